@@ -49,6 +49,9 @@ if response.status_code == 200:
     # Extract the data using re.findall()
     stuff = soup.find_all('pre')[0].contents[0]
     lists_of_data = re.findall(pattern, stuff)
+    # The rates per century sit on the line under each body's elements, with no name in front
+    rate_pattern = r'^\s+([\d.-]+)\s+([\d.-]+)\s+([\d.-]+)\s+([\d.-]+)\s+([\d.-]+)\s+([\d.-]+)\s*$'
+    lists_of_rates = re.findall(rate_pattern, stuff, re.MULTILINE)
     base_data1 = [data.contents for i, (data) in enumerate(table1.find_all('tbody')[0].contents[:-1]) if i % 2 != 0]
     # fixing base_data1
     base_data1 = [[data[1].text.strip(), re.sub("\n|\s+","",data[5].text.strip()), re.sub("\n\s+","",data[7].text.strip())] for data in base_data1]
@@ -67,6 +70,10 @@ if response.status_code == 200:
         # Filling in the dictionaries
         for j, (val) in enumerate(new_data):
             data[name_of_body][headers[j]] = val
+        # Rates get the same conversion and headers, in their own table
+        rates = [float(val) for val in lists_of_rates[i]]
+        rates[0] = round(rates[0]*149597870.7, 1)
+        data[name_of_body]["rates_per_century"] = {headers[j]: val for j, (val) in enumerate(rates)}
         for j, (val) in enumerate(physical_data[i][1:]):
             if j == 0:
                 data[name_of_body][headers1[j].lower() + "_km"] = val
@@ -75,7 +82,9 @@ if response.status_code == 200:
         rows.append(row_data)
     
     # Define some more toml headers
-    toml_dict = {"SolarSystem": data, "number_of_bodies": len(data)}
+    # The table's elements are for J2000 and valid from 1800 AD to 2050 AD
+    toml_dict = {"SolarSystem": data, "number_of_bodies": len(data), "epoch_jd": 2451545.0,
+                 "valid_from_jd": 2378496.5, "valid_to_jd": 2470172.5}
 
     # Print the table headers
     toml_str = toml.dumps(toml_dict)
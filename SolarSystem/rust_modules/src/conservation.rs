@@ -0,0 +1,193 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::integrators::Integrator;
+use crate::orbit_propagration::{gravitational_parameter, integrate_n_body, ForceModel};
+use crate::planet::SolarSystem;
+use crate::units::{AngularMomentum, Energy, LinearMomentum, Mass, Time};
+use crate::vector::{add, cross, dot, norm, scale, sub};
+
+/// Totals over the Sun and every body at one step of an N-body run, with how far each has wandered from the start
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConservationSample {
+    pub time: Time, // past the system epoch
+    pub energy: Energy, // kinetic plus point mass potential
+    pub linear_momentum: LinearMomentum,
+    pub angular_momentum: AngularMomentum, // about the origin
+    pub energy_drift: f64, // |E - E0| / |E0|
+    pub linear_momentum_drift: f64, // |P - P0| / sum of m |v| at the start, the total itself can be close to zero
+    pub angular_momentum_drift: f64, // |L - L0| / |L0|
+}
+
+/// Energy and momentum of a whole N-body run, one sample per output step starting with the initial state
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConservationReport {
+    pub samples: Vec<ConservationSample>,
+}
+
+/// Energy, linear momentum and angular momentum of a set of point masses
+fn totals(masses: &[Mass], positions: &[[f64; 3]], velocities: &[[f64; 3]]) -> (Energy, LinearMomentum, AngularMomentum) {
+    let mut energy = 0.0;
+    let mut linear_momentum = [0.0; 3];
+    let mut angular_momentum = [0.0; 3];
+    for i in 0..masses.len() {
+        let mass = masses[i].in_kg();
+        let momentum = scale(velocities[i], mass);
+        energy += 0.5 * dot(momentum, velocities[i]);
+        linear_momentum = add(linear_momentum, momentum);
+        angular_momentum = add(angular_momentum, cross(positions[i], momentum));
+        for j in (i + 1)..masses.len() {
+            let distance = norm(sub(positions[j], positions[i]));
+            energy -= gravitational_parameter(masses[j]).in_km3_per_s2() * mass / distance;
+        }
+    }
+    (
+        Energy::kg_km2_per_s2(energy),
+        LinearMomentum::kg_km_per_s(linear_momentum),
+        AngularMomentum::kg_km2_per_s(angular_momentum),
+    )
+}
+
+impl ConservationReport {
+    /// Largest relative energy drift over the run
+    pub fn max_energy_drift(&self) -> f64 {
+        self.samples.iter().map(|sample| sample.energy_drift).fold(0.0, f64::max)
+    }
+
+    /// Largest relative linear momentum drift over the run
+    pub fn max_linear_momentum_drift(&self) -> f64 {
+        self.samples.iter().map(|sample| sample.linear_momentum_drift).fold(0.0, f64::max)
+    }
+
+    /// Largest relative angular momentum drift over the run
+    pub fn max_angular_momentum_drift(&self) -> f64 {
+        self.samples.iter().map(|sample| sample.angular_momentum_drift).fold(0.0, f64::max)
+    }
+
+    /// Comma separated values, one row per step. Lines starting with # before the column names give the units
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "# units: time s past the epoch, energy kg km^2/s^2, linear momentum kg km/s, angular momentum kg km^2/s")?;
+        writeln!(writer, "# drifts are relative to the first row")?;
+        writeln!(
+            writer,
+            "time_s,energy,px,py,pz,lx,ly,lz,energy_drift,linear_momentum_drift,angular_momentum_drift"
+        )?;
+        for sample in &self.samples {
+            let [px, py, pz] = sample.linear_momentum.in_kg_km_per_s();
+            let [lx, ly, lz] = sample.angular_momentum.in_kg_km2_per_s();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                sample.time.in_seconds(),
+                sample.energy.in_kg_km2_per_s2(),
+                px,
+                py,
+                pz,
+                lx,
+                ly,
+                lz,
+                sample.energy_drift,
+                sample.linear_momentum_drift,
+                sample.angular_momentum_drift
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Pretty printed JSON of every sample
+    pub fn write_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *writer, self)?;
+        writeln!(writer)
+    }
+}
+
+/// `propagate_n_body`, also keeping track of the total energy and momentum of the Sun and every body at the start
+/// and after each step. Only point mass gravity goes into the energy, so zonal harmonics, relativity and the
+/// non-gravitational forces all show up as drift along with the integrator's own error.
+/// Comparing runs with different integrators or step sizes on the same span shows which is good enough.
+/// Only the starting sample is taken unless `step` is positive
+pub fn propagate_n_body_with_diagnostics(
+    system: &mut SolarSystem,
+    integrator: &mut dyn Integrator,
+    forces: &ForceModel,
+    time_span: Time,
+    step: Time,
+) -> ConservationReport {
+    let mut samples: Vec<ConservationSample> = Vec::new();
+    let mut momentum_scale = 0.0;
+    integrate_n_body(system, integrator, forces, time_span, step, |time, masses, positions, velocities| {
+        let (energy, linear_momentum, angular_momentum) = totals(masses, positions, velocities);
+        let (energy_drift, linear_momentum_drift, angular_momentum_drift) = match samples.first() {
+            Some(first) => (
+                ((energy - first.energy) / first.energy).abs(),
+                norm(sub(linear_momentum.in_kg_km_per_s(), first.linear_momentum.in_kg_km_per_s())) / momentum_scale,
+                norm(sub(angular_momentum.in_kg_km2_per_s(), first.angular_momentum.in_kg_km2_per_s()))
+                    / norm(first.angular_momentum.in_kg_km2_per_s()),
+            ),
+            None => {
+                momentum_scale = masses.iter().zip(velocities).map(|(mass, velocity)| mass.in_kg() * norm(*velocity)).sum();
+                (0.0, 0.0, 0.0)
+            }
+        };
+        samples.push(ConservationSample {
+            time,
+            energy,
+            linear_momentum,
+            angular_momentum,
+            energy_drift,
+            linear_momentum_drift,
+            angular_momentum_drift,
+        });
+    });
+    ConservationReport { samples }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrators::{DormandPrince45, Leapfrog, RungeKutta4};
+    use crate::planet::DEFAULT_DATA_PATH;
+
+    /// A year of the shipped system under point mass gravity, sampled daily
+    fn year_with(integrator: &mut dyn Integrator) -> ConservationReport {
+        let mut system = SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load");
+        propagate_n_body_with_diagnostics(&mut system, integrator, &ForceModel::default(), Time::days(365.25), Time::days(1.0))
+    }
+
+    #[test]
+    fn totals_of_two_bodies() {
+        let masses = [Mass::kg(2E24), Mass::kg(1E22)];
+        let (positions, velocities) = ([[0.0; 3], [1E5, 0.0, 0.0]], [[0.0; 3], [0.0, 2.0, 0.0]]);
+        let (energy, linear_momentum, angular_momentum) = totals(&masses, &positions, &velocities);
+        let expected = 0.5 * 1E22 * 4.0 - gravitational_parameter(masses[0]).in_km3_per_s2() * 1E22 / 1E5;
+        assert!((energy.in_kg_km2_per_s2() - expected).abs() < 1E-12 * expected.abs(), "{} instead of {}", energy, expected);
+        assert_eq!(linear_momentum.in_kg_km_per_s(), [0.0, 2E22, 0.0]);
+        assert_eq!(angular_momentum.in_kg_km2_per_s(), [0.0, 0.0, 2E27]);
+    }
+
+    #[test]
+    fn runge_kutta_drift_is_small_and_shrinks_with_the_step() {
+        let daily = year_with(&mut RungeKutta4::new(Time::days(1.0)));
+        assert_eq!(daily.samples.len(), 367);
+        let first = &daily.samples[0];
+        assert_eq!((first.time, first.energy_drift, first.linear_momentum_drift), (Time::default(), 0.0, 0.0));
+        assert!(daily.max_energy_drift() < 1E-7, "energy drift {}", daily.max_energy_drift());
+        assert!(daily.max_linear_momentum_drift() < 1E-12, "momentum drift {}", daily.max_linear_momentum_drift());
+        assert!(daily.max_angular_momentum_drift() < 1E-9, "angular momentum drift {}", daily.max_angular_momentum_drift());
+        // Fourth order, so halving the step should cut the drift by well over ten
+        let twice_daily = year_with(&mut RungeKutta4::new(Time::days(0.5)));
+        let (finer, coarser) = (twice_daily.max_energy_drift(), daily.max_energy_drift());
+        assert!(finer * 16.0 < coarser, "{} against {}", finer, coarser);
+        let adaptive = year_with(&mut DormandPrince45::new(1E-10, 1E-6));
+        assert!(adaptive.max_energy_drift() < 1E-10, "energy drift {}", adaptive.max_energy_drift());
+    }
+
+    #[test]
+    fn leapfrog_keeps_angular_momentum() {
+        let report = year_with(&mut Leapfrog::new(Time::days(1.0)));
+        assert!(report.max_angular_momentum_drift() < 1E-12, "angular momentum drift {}", report.max_angular_momentum_drift());
+        // Only second order, but the energy error stays bounded rather than growing
+        assert!(report.max_energy_drift() < 1E-5, "energy drift {}", report.max_energy_drift());
+    }
+}
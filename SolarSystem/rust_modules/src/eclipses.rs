@@ -0,0 +1,449 @@
+use std::f64::consts::PI;
+
+use crate::encounters::radius_of;
+use crate::frames::State;
+use crate::horizons::interpolate;
+use crate::planet::SolarSystem;
+use crate::units::{JulianDate, Time};
+use crate::vector::{add, dot, norm, scale, sub};
+
+/// Contact and maximum times are refined until they're known to this many seconds
+const TIME_TOLERANCE: f64 = 1E-1;
+const MAX_BISECTIONS: usize = 100;
+/// Time offset (s) for the numerical slope of the separation when looking for its minimum
+const SLOPE_STEP: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    SolarEclipse, // a moon's shadow falls on its planet
+    LunarEclipse, // a moon passes through its planet's shadow
+    Transit, // a body crosses the Sun's disc as seen from the observer
+    Occultation, // a body hides another as seen from the observer
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EclipseType {
+    Partial, // the discs overlap, or a moon's penumbra touches its planet
+    Penumbral, // a moon only passes through the penumbra of its planet's shadow
+    Umbral, // part of a moon passes through the umbra of its planet's shadow
+    Annular, // the foreground disc lies wholly inside the background one, or a moon's antumbra touches its planet
+    Total, // the background is wholly hidden, or a moon's umbra touches its planet or a moon lies wholly in the umbra
+}
+
+/// One eclipse, transit or occultation. The observer sees the foreground body pass in front of the background one:
+/// the eclipsed planet or moon sees its moon or planet cross the Sun.
+/// The magnitude is the fraction of the background's diameter covered, or the ratio of the two diameters once one disc
+/// is inside the other. For lunar eclipses it's the fraction of the moon's diameter in the umbra (penumbra for penumbral ones)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EclipseEvent {
+    pub kind: EventKind,
+    pub eclipse_type: EclipseType, // at maximum
+    pub observer: String,
+    pub foreground: String,
+    pub background: String,
+    pub start: JulianDate, // first contact
+    pub maximum: JulianDate,
+    pub end: JulianDate, // last contact
+    pub magnitude: f64, // at maximum
+}
+
+/// Heliocentric histories of a few bodies, interpolated between the stored samples
+struct Tracks {
+    times: Vec<Time>, // past the system epoch
+    states: Vec<Vec<([f64; 3], [f64; 3])>>,
+}
+
+impl Tracks {
+    fn new(system: &SolarSystem, names: &[&str]) -> Option<Self> {
+        let times = system.bodies.values().next()?.times.clone();
+        let states = names
+            .iter()
+            .map(|name| Some(system.heliocentric_states(name)?.iter().map(State::vectors).collect()))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { times, states })
+    }
+
+    /// Positions (km) of the bodies at `time` (s past the system epoch), which must be in the stored span
+    fn positions(&self, time: f64) -> Vec<[f64; 3]> {
+        let time = Time::seconds(time);
+        self.states.iter().map(|states| interpolate(&self.times, states, time).map_or([f64::NAN; 3], |(position, _)| position)).collect()
+    }
+
+    /// The sample times in seconds past the system epoch, for `find_events`
+    fn seconds(&self) -> Vec<f64> {
+        self.times.iter().map(|time| time.in_seconds()).collect()
+    }
+}
+
+/// Where a body sits in the shadow the Sun casts behind an occulting body, measured in the plane through the body
+/// at right angles to the shadow's axis
+struct Shadow {
+    behind: bool, // on the far side of the occulter from the Sun
+    axis_distance: f64, // km from the axis to the body's centre
+    umbra: f64, // km, radius of the umbra, negative beyond its tip where it becomes the antumbra
+    penumbra: f64, // km, radius of the penumbra
+    along_axis: f64, // km from the occulter to the plane
+}
+
+impl Shadow {
+    fn new(sun_radius: f64, occulter: [f64; 3], occulter_radius: f64, target: [f64; 3]) -> Self {
+        let sun_distance = norm(occulter);
+        let axis = scale(occulter, 1.0 / sun_distance);
+        let offset = sub(target, occulter);
+        let along_axis = dot(offset, axis);
+        Self {
+            behind: along_axis > 0.0,
+            axis_distance: norm(sub(offset, scale(axis, along_axis))),
+            umbra: occulter_radius - along_axis * (sun_radius - occulter_radius) / sun_distance,
+            penumbra: occulter_radius + along_axis * (sun_radius + occulter_radius) / sun_distance,
+            along_axis,
+        }
+    }
+
+    /// How far (km) a body of `radius` is from touching the penumbra, negative once inside it
+    fn penumbra_gap(&self, radius: f64) -> f64 {
+        if self.behind { self.axis_distance - self.penumbra - radius } else { self.axis_distance.max(radius) }
+    }
+}
+
+/// Apparent radii (radians) of the background and foreground discs seen from the observer, and the angle between their
+/// centres. None if the foreground body isn't nearer the observer than the background one
+fn discs(observer: [f64; 3], foreground: [f64; 3], foreground_radius: f64, background: [f64; 3], background_radius: f64) -> Option<(f64, f64, f64)> {
+    let (to_foreground, to_background) = (sub(foreground, observer), sub(background, observer));
+    let (foreground_distance, background_distance) = (norm(to_foreground), norm(to_background));
+    if foreground_distance >= background_distance {
+        return None;
+    }
+    let background_disc = (background_radius / background_distance).clamp(-1.0, 1.0).asin();
+    let foreground_disc = (foreground_radius / foreground_distance).clamp(-1.0, 1.0).asin();
+    let separation = (dot(to_foreground, to_background) / (foreground_distance * background_distance)).clamp(-1.0, 1.0).acos();
+    Some((background_disc, foreground_disc, separation))
+}
+
+/// Type and magnitude of an overlap of two discs, from `discs`. While the edges cross the magnitude is the fraction of
+/// the background's diameter covered, once one disc is inside the other it's the ratio of their diameters
+fn classify_discs((background, foreground, separation): (f64, f64, f64)) -> (EclipseType, f64) {
+    if foreground >= background && separation <= foreground - background {
+        (EclipseType::Total, foreground / background)
+    }
+    else if foreground < background && separation <= background - foreground {
+        (EclipseType::Annular, foreground / background)
+    }
+    else {
+        (EclipseType::Partial, (background + foreground - separation) / (2.0 * background))
+    }
+}
+
+/// Start, maximum and end of each stretch of `times` (s past the epoch) where `gap` is negative.
+/// Each stretch is found from the minimum of `gap` between two samples, so it can't hold more than one minimum
+/// per step, and stretches cut off by the start or end of the span aren't reported
+pub(crate) fn find_events(times: &[f64], gap: impl Fn(f64) -> f64) -> Vec<(f64, f64, f64)> {
+    let (Some(&first), Some(&last)) = (times.first(), times.last()) else { return Vec::new() };
+    let slope = |time: f64| {
+        let (before, after) = ((time - SLOPE_STEP).max(first), (time + SLOPE_STEP).min(last));
+        gap(after) - gap(before)
+    };
+    // Bisection for where `test` turns from false to true between `low` and `high`
+    let bisect = |mut low: f64, mut high: f64, test: &dyn Fn(f64) -> bool| {
+        for _ in 0..MAX_BISECTIONS {
+            if high - low < TIME_TOLERANCE {
+                break;
+            }
+            let middle = 0.5 * (low + high);
+            if test(middle) {
+                high = middle;
+            }
+            else {
+                low = middle;
+            }
+        }
+        0.5 * (low + high)
+    };
+    let mut events = Vec::new();
+    for i in 0..times.len().saturating_sub(1) {
+        if slope(times[i]) >= 0.0 || slope(times[i + 1]) < 0.0 {
+            continue;
+        }
+        let maximum = bisect(times[i], times[i + 1], &|time| slope(time) >= 0.0);
+        if gap(maximum) >= 0.0 {
+            continue;
+        }
+        // Last sample before the maximum and first after it that are clear of the event
+        let Some(before) = times[..=i].iter().rev().find(|time| gap(**time) >= 0.0) else { continue };
+        let Some(after) = times[i + 1..].iter().find(|time| gap(**time) >= 0.0) else { continue };
+        let start = bisect(*before, maximum, &|time| gap(time) < 0.0);
+        let end = bisect(maximum, *after, &|time| gap(time) >= 0.0);
+        events.push((start, maximum, end));
+    }
+    events
+}
+
+/// Moons of the system paired with the planet (or moon) they orbit
+fn moons(system: &SolarSystem) -> Vec<(String, String)> {
+    system
+        .body_names()
+        .into_iter()
+        .filter_map(|name| Some((system.parent_name(&name).filter(|parent| *parent != system.central_body.name)?, name)))
+        .collect()
+}
+
+fn julian_date(system: &SolarSystem, seconds: f64) -> JulianDate {
+    system.epoch + Time::seconds(seconds)
+}
+
+/// Eclipses of the Sun seen from each planet (or moon) with moons, over the stored samples. The event lasts while the
+/// moon's penumbra touches any part of the planet and its type and magnitude are those at the point of the planet's
+/// surface nearest the shadow's axis at the time of maximum. The step should be short next to the eclipses
+pub fn solar_eclipses(system: &SolarSystem) -> Vec<EclipseEvent> {
+    let sun_radius = system.central_body.radius.in_km();
+    let mut events = Vec::new();
+    for (planet, moon) in moons(system) {
+        let (Some(planet_radius), Some(moon_radius)) = (radius_of(system, &planet), radius_of(system, &moon)) else { continue };
+        let (planet_radius, moon_radius) = (planet_radius.in_km(), moon_radius.in_km());
+        let Some(tracks) = Tracks::new(system, &[&planet, &moon]) else { continue };
+        let shadow = |time: f64| {
+            let positions = tracks.positions(time);
+            (Shadow::new(sun_radius, positions[1], moon_radius, positions[0]), positions)
+        };
+        for (start, maximum, end) in find_events(&tracks.seconds(), |time| shadow(time).0.penumbra_gap(planet_radius)) {
+            let (shadow, positions) = shadow(maximum);
+            let (planet_position, moon_position) = (positions[0], positions[1]);
+            // The point of the planet's surface nearest the axis
+            let axis = scale(moon_position, 1.0 / norm(moon_position));
+            let on_axis = scale(axis, norm(moon_position) + shadow.along_axis);
+            let point = if shadow.axis_distance > planet_radius {
+                let towards_axis = scale(sub(on_axis, planet_position), planet_radius / shadow.axis_distance);
+                add(planet_position, towards_axis)
+            }
+            else {
+                let depth = (planet_radius * planet_radius - shadow.axis_distance * shadow.axis_distance).sqrt();
+                sub(on_axis, scale(axis, depth))
+            };
+            let Some(discs) = discs(point, moon_position, moon_radius, [0.0; 3], sun_radius) else { continue };
+            let (_, magnitude) = classify_discs(discs);
+            let eclipse_type = if shadow.umbra > 0.0 && shadow.axis_distance < shadow.umbra + planet_radius {
+                EclipseType::Total
+            }
+            else if shadow.umbra <= 0.0 && shadow.axis_distance < planet_radius - shadow.umbra {
+                EclipseType::Annular
+            }
+            else {
+                EclipseType::Partial
+            };
+            events.push(EclipseEvent {
+                kind: EventKind::SolarEclipse,
+                eclipse_type,
+                observer: planet.clone(),
+                foreground: moon.clone(),
+                background: system.central_body.name.clone(),
+                start: julian_date(system, start),
+                maximum: julian_date(system, maximum),
+                end: julian_date(system, end),
+                magnitude,
+            });
+        }
+    }
+    events.sort_by(|a, b| a.start.total_cmp(&b.start));
+    events
+}
+
+/// Eclipses of each moon by its planet's shadow over the stored samples, lasting while any part of the moon is in the
+/// penumbra. The magnitude is the fraction of the moon's diameter inside the umbra, or for penumbral eclipses inside
+/// the penumbra. The step should be short next to the eclipses
+pub fn lunar_eclipses(system: &SolarSystem) -> Vec<EclipseEvent> {
+    let sun_radius = system.central_body.radius.in_km();
+    let mut events = Vec::new();
+    for (planet, moon) in moons(system) {
+        let (Some(planet_radius), Some(moon_radius)) = (radius_of(system, &planet), radius_of(system, &moon)) else { continue };
+        let (planet_radius, moon_radius) = (planet_radius.in_km(), moon_radius.in_km());
+        let Some(tracks) = Tracks::new(system, &[&planet, &moon]) else { continue };
+        let shadow = |time: f64| {
+            let positions = tracks.positions(time);
+            Shadow::new(sun_radius, positions[0], planet_radius, positions[1])
+        };
+        for (start, maximum, end) in find_events(&tracks.seconds(), |time| shadow(time).penumbra_gap(moon_radius)) {
+            let shadow = shadow(maximum);
+            let umbral_magnitude = (shadow.umbra + moon_radius - shadow.axis_distance) / (2.0 * moon_radius);
+            let (eclipse_type, magnitude) = if shadow.axis_distance + moon_radius <= shadow.umbra {
+                (EclipseType::Total, umbral_magnitude)
+            }
+            else if umbral_magnitude > 0.0 {
+                (EclipseType::Umbral, umbral_magnitude)
+            }
+            else {
+                (EclipseType::Penumbral, (shadow.penumbra + moon_radius - shadow.axis_distance) / (2.0 * moon_radius))
+            };
+            events.push(EclipseEvent {
+                kind: EventKind::LunarEclipse,
+                eclipse_type,
+                observer: moon.clone(),
+                foreground: planet.clone(),
+                background: system.central_body.name.clone(),
+                start: julian_date(system, start),
+                maximum: julian_date(system, maximum),
+                end: julian_date(system, end),
+                magnitude,
+            });
+        }
+    }
+    events.sort_by(|a, b| a.start.total_cmp(&b.start));
+    events
+}
+
+/// Times `foreground` passes in front of `background` as seen from the centre of `observer` over the stored samples
+fn disc_events(system: &SolarSystem, kind: EventKind, observer: &str, foreground: &str, background: &str) -> Option<Vec<EclipseEvent>> {
+    let (foreground_radius, background_radius) = (radius_of(system, foreground)?.in_km(), radius_of(system, background)?.in_km());
+    let tracks = Tracks::new(system, &[observer, foreground, background])?;
+    let discs = |time: f64| {
+        let positions = tracks.positions(time);
+        discs(positions[0], positions[1], foreground_radius, positions[2], background_radius)
+    };
+    // Nothing can happen while the foreground body is the further of the two
+    let gap = |time: f64| discs(time).map_or(PI, |(background, foreground, separation)| separation - background - foreground);
+    let events = find_events(&tracks.seconds(), gap);
+    Some(
+        events
+            .into_iter()
+            .filter_map(|(start, maximum, end)| {
+                let (eclipse_type, magnitude) = classify_discs(discs(maximum)?);
+                Some(EclipseEvent {
+                    kind,
+                    eclipse_type,
+                    observer: observer.to_string(),
+                    foreground: foreground.to_string(),
+                    background: background.to_string(),
+                    start: julian_date(system, start),
+                    maximum: julian_date(system, maximum),
+                    end: julian_date(system, end),
+                    magnitude,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Transits of every other body across the Sun as seen from the centre of `observer` over the stored samples.
+/// Annular transits are those where the whole body is inside the Sun's disc. None if the observer isn't in the system
+pub fn transits(system: &SolarSystem, observer: &str) -> Option<Vec<EclipseEvent>> {
+    system.find(observer)?;
+    let sun = &system.central_body.name;
+    let mut events: Vec<EclipseEvent> = system
+        .body_names()
+        .iter()
+        .filter(|name| *name != observer)
+        .filter_map(|name| disc_events(system, EventKind::Transit, observer, name, sun))
+        .flatten()
+        .collect();
+    events.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Some(events)
+}
+
+/// Occultations of one body by another as seen from the centre of `observer` over the stored samples, the Sun
+/// excepted (see `transits` and `solar_eclipses`). None if the observer isn't in the system
+pub fn occultations(system: &SolarSystem, observer: &str) -> Option<Vec<EclipseEvent>> {
+    system.find(observer)?;
+    let names: Vec<String> = system.body_names().into_iter().filter(|name| name != observer).collect();
+    let mut events = Vec::new();
+    for foreground in &names {
+        for background in names.iter().filter(|background| *background != foreground) {
+            events.extend(disc_events(system, EventKind::Occultation, observer, foreground, background).into_iter().flatten());
+        }
+    }
+    events.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Some(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planet::{Body, DEFAULT_DATA_PATH};
+    use crate::units::Length;
+    use std::collections::HashMap;
+
+    /// Gives `body` samples every `step` seconds up to `span`, moving in a straight line from `start` at `velocity`
+    fn straight_line(body: &mut Body, start: [f64; 3], velocity: [f64; 3], span: f64, step: f64) {
+        let times: Vec<f64> = (0..=(span / step) as usize).map(|i| i as f64 * step).collect();
+        body.coords = times.iter().map(|time| add(start, scale(velocity, *time))).collect();
+        body.vel = vec![velocity; times.len()];
+        body.times = times.into_iter().map(Time::seconds).collect();
+    }
+
+    /// Mars alone and still, 2e8 km from the Sun along x
+    fn mars_alone(span: f64, step: f64) -> SolarSystem {
+        let mut system = SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load");
+        system.bodies.retain(|name, _| name == "Mars" || name == "Venus");
+        straight_line(system.bodies.get_mut("Mars").expect("Mars is in the system"), [2E8, 0.0, 0.0], [0.0; 3], span, step);
+        system
+    }
+
+    fn seconds_past_epoch(system: &SolarSystem, date: JulianDate) -> f64 {
+        (date - system.epoch).in_seconds()
+    }
+
+    #[test]
+    fn events_are_bracketed_by_their_contacts() {
+        let times: Vec<f64> = (0..=10).map(|i| i as f64 * 100.0).collect();
+        let events = find_events(&times, |time| (time - 520.0).abs() - 130.0);
+        let [(start, maximum, end)] = events.as_slice() else { panic!("expected one event, got {:?}", events) };
+        for (found, expected) in [(start, 390.0), (maximum, 520.0), (end, 650.0)] {
+            assert!((found - expected).abs() < TIME_TOLERANCE, "{} instead of {}", found, expected);
+        }
+        // Already under way at the first sample
+        assert!(find_events(&times, |time| time - 300.0).is_empty());
+    }
+
+    #[test]
+    fn moon_crossing_the_shadow_axis_is_totally_eclipsed() {
+        let (span, step, speed) = (20000.0, 500.0, 1.0);
+        let mut system = mars_alone(span, step);
+        system.bodies.remove("Venus");
+        let mut moon = system.find("Mars").expect("Mars is in the system").clone();
+        moon.radius = Length::km(100.0);
+        // 1e4 km behind Mars, crossing the shadow's axis halfway through
+        straight_line(&mut moon, [1E4, -speed * span / 2.0, 0.0], [0.0, speed, 0.0], span, step);
+        let mars = system.bodies.get_mut("Mars").expect("Mars is in the system");
+        mars.moons = Some(HashMap::from([("Phobos".to_string(), moon)]));
+
+        let events = lunar_eclipses(&system);
+        let [eclipse] = events.as_slice() else { panic!("expected one eclipse, got {:?}", events) };
+        assert_eq!((eclipse.kind, eclipse.eclipse_type), (EventKind::LunarEclipse, EclipseType::Total));
+        assert_eq!((eclipse.observer.as_str(), eclipse.foreground.as_str()), ("Phobos", "Mars"));
+        // The penumbra widens linearly behind Mars, so first contact is where the moon's edge meets it
+        let (sun_radius, mars_radius) = (system.central_body.radius.in_km(), radius_of(&system, "Mars").expect("Mars").in_km());
+        let penumbra = mars_radius + 1E4 * (sun_radius + mars_radius) / 2E8;
+        let half = (penumbra + 100.0) / speed;
+        for (date, expected) in [(eclipse.start, span / 2.0 - half), (eclipse.maximum, span / 2.0), (eclipse.end, span / 2.0 + half)] {
+            let found = seconds_past_epoch(&system, date);
+            assert!((found - expected).abs() < 2.0 * TIME_TOLERANCE, "{} s instead of {} s", found, expected);
+        }
+        // The maximum is only known to a tenth of a second, a twentieth of a km off the axis
+        let umbra = mars_radius - 1E4 * (sun_radius - mars_radius) / 2E8;
+        assert!((eclipse.magnitude - (umbra + 100.0) / 200.0).abs() < speed * TIME_TOLERANCE / 200.0, "magnitude {}", eclipse.magnitude);
+    }
+
+    #[test]
+    fn venus_transit_seen_from_mars_is_centred_and_symmetric() {
+        let (span, step, speed) = (100000.0, 2000.0, 10.0);
+        let mut system = mars_alone(span, step);
+        let venus = system.bodies.get_mut("Venus").expect("Venus is in the system");
+        straight_line(venus, [1E8, -speed * span / 2.0, 0.0], [0.0, speed, 0.0], span, step);
+        let events = transits(&system, "Mars").expect("Mars is in the system");
+        let [transit] = events.as_slice() else { panic!("expected one transit, got {:?}", events) };
+        assert_eq!((transit.kind, transit.eclipse_type), (EventKind::Transit, EclipseType::Annular));
+        assert_eq!(transit.foreground, "Venus");
+
+        // Contact when the angle between the centres is the two apparent radii together
+        let (sun_radius, venus_radius) = (system.central_body.radius.in_km(), radius_of(&system, "Venus").expect("Venus").in_km());
+        let mut offset = 0.0;
+        for _ in 0..10 {
+            let venus_disc = (venus_radius / (1E8f64.powi(2) + offset * offset).sqrt()).asin();
+            offset = 1E8 * ((sun_radius / 2E8).asin() + venus_disc).tan();
+        }
+        let half = offset / speed;
+        for (date, expected) in [(transit.start, span / 2.0 - half), (transit.maximum, span / 2.0), (transit.end, span / 2.0 + half)] {
+            let found = seconds_past_epoch(&system, date);
+            assert!((found - expected).abs() < 2.0 * TIME_TOLERANCE, "{} s instead of {} s", found, expected);
+        }
+        assert!(transits(&system, "Vulcan").is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use crate::frames::{Frame, State};
+use crate::horizons::interpolate;
+use crate::integrators::Integrator;
+use crate::orbit_propagration::{output_offsets, propagate_n_body, ForceModel};
+use crate::planet::{Body, SolarSystem};
+use crate::units::{JulianDate, Length, Time};
+use crate::vector::{add, dot, norm, scale, sub};
+
+/// Times of closest approach are refined until they're known to this
+const MINIMUM_TOLERANCE: Time = Time::seconds(1E-3);
+const MAX_BISECTIONS: usize = 100;
+
+/// Two bodies at their closest during one pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloseApproach {
+    pub first: String,
+    pub second: String,
+    pub julian_date: JulianDate,
+    pub time: Time, // past the system epoch
+    pub distance: Length, // between the centres
+    pub relative_state: State, // of the second body seen from the first, in the ecliptic frame centred on the first
+    pub collision: bool, // the distance is less than the two radii together
+}
+
+/// Radius of a body or of the central body
+pub(crate) fn radius_of(system: &SolarSystem, name: &str) -> Option<Length> {
+    if name == system.central_body.name {
+        return Some(system.central_body.radius);
+    }
+    system.find(name).map(|body| body.radius)
+}
+
+/// Radius of a sphere with the volume of two others together
+fn combined_radius(first: Length, second: Length) -> Length {
+    Length::km((first.in_km().powi(3) + second.in_km().powi(3)).cbrt())
+}
+
+/// Every body plus the central body, which the close approach search treats like any other
+fn candidates(system: &SolarSystem) -> Vec<String> {
+    let mut names = vec![system.central_body.name.clone()];
+    names.extend(system.body_names());
+    names
+}
+
+/// The closest approach of two bodies between stored samples `index` and `index + 1`, if they reach their minimum
+/// distance in that interval and it's under `threshold` or close enough for them to touch.
+/// The relative motion between the samples is a cubic Hermite fit and its minimum is found by bisection
+fn approach_in_interval(system: &SolarSystem, first: &str, second: &str, times: &[Time], index: usize, threshold: Length) -> Option<CloseApproach> {
+    let states = [system.relative_state(second, first, index)?.vectors(), system.relative_state(second, first, index + 1)?.vectors()];
+    let times = [times[index], times[index + 1]];
+    // Closing in at the start of the interval and moving apart (or just stopped closing) by the end
+    let closing = |(position, velocity): ([f64; 3], [f64; 3])| dot(position, velocity) < 0.0;
+    if !closing(states[0]) || closing(states[1]) {
+        return None;
+    }
+    let (mut low, mut high) = (times[0], times[1]);
+    for _ in 0..MAX_BISECTIONS {
+        if high - low < MINIMUM_TOLERANCE {
+            break;
+        }
+        let middle = 0.5 * (low + high);
+        if closing(interpolate(&times, &states, middle)?) {
+            low = middle;
+        }
+        else {
+            high = middle;
+        }
+    }
+    let time = 0.5 * (low + high);
+    let (position, velocity) = interpolate(&times, &states, time)?;
+    let distance = Length::km(norm(position));
+    let collision = distance < radius_of(system, first)? + radius_of(system, second)?;
+    (distance < threshold || collision).then(|| CloseApproach {
+        first: first.to_string(),
+        second: second.to_string(),
+        julian_date: system.epoch + time,
+        time,
+        distance,
+        relative_state: State { frame: Frame::ecliptic(first), time, position, velocity },
+        collision,
+    })
+}
+
+/// Close approaches in the stored interval starting at sample `index`, in time order
+fn approaches_at(system: &SolarSystem, index: usize, threshold: Length) -> Vec<CloseApproach> {
+    let Some(times) = system.bodies.values().next().map(|body| body.times.clone()) else { return Vec::new() };
+    if index + 1 >= times.len() {
+        return Vec::new();
+    }
+    let names = candidates(system);
+    let mut approaches = Vec::new();
+    for (i, first) in names.iter().enumerate() {
+        for second in &names[i + 1..] {
+            approaches.extend(approach_in_interval(system, first, second, &times, index, threshold));
+        }
+    }
+    approaches.sort_by(|a, b| a.time.in_seconds().total_cmp(&b.time.in_seconds()));
+    approaches
+}
+
+/// Every time two bodies (the central body included) came within `threshold` of each other over the stored samples,
+/// refined to the moment of closest approach to within a millisecond, in time order. Passes that are still closing at
+/// the last sample or were already moving apart at the first aren't reported, and a pair can only be caught once
+/// per step, so the step should be short next to the quickest encounter of interest
+pub fn close_approaches(system: &SolarSystem, threshold: Length) -> Vec<CloseApproach> {
+    (0..system.sample_count().saturating_sub(1)).flat_map(|index| approaches_at(system, index, threshold)).collect()
+}
+
+/// Recursive search for a body at any depth, for changing it in place
+fn find_mut<'a>(bodies: &'a mut HashMap<String, Body>, name: &str) -> Option<&'a mut Body> {
+    if bodies.contains_key(name) {
+        return bodies.get_mut(name);
+    }
+    bodies.values_mut().filter_map(|body| body.moons.as_mut()).find_map(|moons| find_mut(moons, name))
+}
+
+/// Recursive search for a body at any depth, taking it out of the system
+fn remove(bodies: &mut HashMap<String, Body>, name: &str) -> Option<Body> {
+    if let Some(body) = bodies.remove(name) {
+        return Some(body);
+    }
+    bodies.values_mut().filter_map(|body| body.moons.as_mut()).find_map(|moons| remove(moons, name))
+}
+
+/// Moves the last stored state of every body in `bodies` by the same offset
+fn shift_last(bodies: &mut HashMap<String, Body>, position: [f64; 3], velocity: [f64; 3]) {
+    for body in bodies.values_mut() {
+        if let (Some(last_position), Some(last_velocity)) = (body.coords.last_mut(), body.vel.last_mut()) {
+            *last_position = add(*last_position, position);
+            *last_velocity = add(*last_velocity, velocity);
+        }
+    }
+}
+
+impl SolarSystem {
+    /// Merges two bodies at the last stored sample, as after a collision. The lighter body is absorbed by the heavier one
+    /// (the central body and a moon's parent always survive), which moves to their centre of mass with their combined
+    /// momentum, mass and volume.
+    /// The absorbed body's history is dropped and its moons go on orbiting the survivor, their histories rebased onto it.
+    /// When the central body absorbs a body every planet is moved so the central body stays at the origin.
+    /// None if either body isn't in the system
+    pub fn merge(&mut self, first: &str, second: &str) -> Option<()> {
+        let mass_of = |name: &str| {
+            if name == self.central_body.name { Some(self.central_body.mass) } else { self.find(name).map(|body| body.orbit_data.mass) }
+        };
+        let (first_mass, second_mass) = (mass_of(first)?, mass_of(second)?);
+        let (mut survivor, mut absorbed) = (first, second);
+        if second == self.central_body.name || (first != self.central_body.name && second_mass > first_mass) {
+            (survivor, absorbed) = (second, first);
+        }
+        // A moon never absorbs its own parent, it would be taken out along with it
+        let parent = self.find(absorbed)?;
+        if self.lineage(survivor).is_some_and(|lineage| lineage.iter().any(|body| std::ptr::eq(*body, parent))) {
+            (survivor, absorbed) = (absorbed, survivor);
+        }
+        let (survivor_mass, absorbed_mass) = (mass_of(survivor)?, mass_of(absorbed)?);
+        let mass = survivor_mass + absorbed_mass;
+        let last = self.find(absorbed)?.coords.len() - 1;
+        let (survivor_position, survivor_velocity) = self.heliocentric_state(survivor, last)?.vectors();
+        let (absorbed_position, absorbed_velocity) = self.heliocentric_state(absorbed, last)?.vectors();
+        let (survivor_share, absorbed_share) = (survivor_mass / mass, absorbed_mass / mass);
+        let position = add(scale(survivor_position, survivor_share), scale(absorbed_position, absorbed_share));
+        let velocity = add(scale(survivor_velocity, survivor_share), scale(absorbed_velocity, absorbed_share));
+        let (position_shift, velocity_shift) = (sub(position, survivor_position), sub(velocity, survivor_velocity));
+
+        // Heliocentric history of the absorbed body and of its new parent, for rebasing the absorbed body's moons
+        let absorbed_states = self.heliocentric_states(absorbed)?;
+        let survivor_states = self.heliocentric_states(survivor)?;
+        let absorbed_radius = radius_of(self, absorbed)?;
+        let mut absorbed_body = remove(&mut self.bodies, absorbed)?;
+        let mut moons = absorbed_body.moons.take().unwrap_or_default();
+        for moon in moons.values_mut() {
+            for (i, (moon_position, moon_velocity)) in moon.coords.iter_mut().zip(moon.vel.iter_mut()).enumerate() {
+                let (Some(from), Some(to)) = (absorbed_states.get(i), survivor_states.get(i)) else { break };
+                *moon_position = add(*moon_position, sub(from.position, to.position));
+                *moon_velocity = add(*moon_velocity, sub(from.velocity, to.velocity));
+            }
+            // The survivor's own last state is about to move
+            if let (Some(last_position), Some(last_velocity)) = (moon.coords.last_mut(), moon.vel.last_mut()) {
+                *last_position = sub(*last_position, position_shift);
+                *last_velocity = sub(*last_velocity, velocity_shift);
+            }
+        }
+
+        if survivor == self.central_body.name {
+            self.central_body.mass = mass;
+            self.central_body.radius = combined_radius(self.central_body.radius, absorbed_radius);
+            shift_last(&mut self.bodies, scale(position_shift, -1.0), scale(velocity_shift, -1.0));
+            self.bodies.extend(moons);
+            return Some(());
+        }
+        let body = find_mut(&mut self.bodies, survivor)?;
+        body.orbit_data.mass = mass;
+        body.radius = combined_radius(body.radius, absorbed_radius);
+        *body.coords.last_mut()? = add(*body.coords.last()?, position_shift);
+        *body.vel.last_mut()? = add(*body.vel.last()?, velocity_shift);
+        // Moons keep their heliocentric states while their parent moves
+        let existing = body.moons.get_or_insert_with(HashMap::new);
+        shift_last(existing, scale(position_shift, -1.0), scale(velocity_shift, -1.0));
+        existing.extend(moons);
+        Some(())
+    }
+}
+
+/// `propagate_n_body` one step at a time, checking every pair of bodies for close approaches under `threshold`
+/// as it goes (see `close_approaches`). With `merge_collisions` set, bodies that collide are merged (see
+/// `SolarSystem::merge`) at the end of the step they collided in and propagation carries on with the merged body.
+/// Returns the approaches in time order, none unless `step` is positive
+pub fn propagate_n_body_with_encounters(
+    system: &mut SolarSystem,
+    integrator: &mut dyn Integrator,
+    forces: &ForceModel,
+    time_span: Time,
+    step: Time,
+    threshold: Length,
+    merge_collisions: bool,
+) -> Vec<CloseApproach> {
+    let mut approaches = Vec::new();
+    let mut previous = Time::default();
+    for offset in output_offsets(time_span, step) {
+        let step = offset - previous;
+        propagate_n_body(system, integrator, forces, step, step);
+        previous = offset;
+        let found = approaches_at(system, system.sample_count().saturating_sub(2), threshold);
+        if merge_collisions {
+            for approach in found.iter().filter(|approach| approach.collision) {
+                // Either body may already have been absorbed by an earlier collision in the same step
+                system.merge(&approach.first, &approach.second);
+            }
+        }
+        approaches.extend(found);
+    }
+    approaches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planet::DEFAULT_DATA_PATH;
+
+    /// Venus and Mars alone around the Sun, with two samples 200 s apart set by hand. Venus sits still while Mars
+    /// passes it in a straight line, closest `miss` km away 100 s in
+    fn flyby(miss: f64) -> SolarSystem {
+        let mut system = SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load");
+        system.bodies.retain(|name, _| name == "Venus" || name == "Mars");
+        let times = vec![Time::default(), Time::seconds(200.0)];
+        let venus = system.bodies.get_mut("Venus").expect("Venus is in the system");
+        (venus.coords, venus.vel, venus.times) = (vec![[1E8, 0.0, 0.0]; 2], vec![[0.0; 3]; 2], times.clone());
+        let mars = system.bodies.get_mut("Mars").expect("Mars is in the system");
+        mars.coords = vec![[1E8 + miss, -1000.0, 0.0], [1E8 + miss, 1000.0, 0.0]];
+        (mars.vel, mars.times) = (vec![[0.0, 10.0, 0.0]; 2], times);
+        system
+    }
+
+    #[test]
+    fn straight_line_pass_is_found_at_its_closest() {
+        let approaches = close_approaches(&flyby(20000.0), Length::km(1E5));
+        let [approach] = approaches.as_slice() else { panic!("expected one approach, got {:?}", approaches) };
+        assert_eq!((approach.first.as_str(), approach.second.as_str()), ("Mars", "Venus"));
+        assert!((approach.time - Time::seconds(100.0)).abs() < MINIMUM_TOLERANCE, "closest at {}", approach.time);
+        assert!((approach.distance - Length::km(20000.0)).abs() < Length::km(1E-3), "{} apart", approach.distance);
+        assert_eq!(approach.relative_state.frame, Frame::ecliptic("Mars"));
+        assert!(!approach.collision);
+        assert!(close_approaches(&flyby(20000.0), Length::km(1E4)).is_empty());
+    }
+
+    #[test]
+    fn pass_inside_both_radii_is_a_collision_whatever_the_threshold() {
+        let approaches = close_approaches(&flyby(5000.0), Length::km(1.0));
+        let [approach] = approaches.as_slice() else { panic!("expected one approach, got {:?}", approaches) };
+        assert!(approach.collision, "{} apart", approach.distance);
+    }
+
+    #[test]
+    fn merging_keeps_mass_momentum_and_volume() {
+        let mut system = flyby(5000.0);
+        let (venus, mars) = (system.find("Venus").expect("Venus").clone(), system.find("Mars").expect("Mars").clone());
+        let (venus_mass, mars_mass) = (venus.orbit_data.mass.in_kg(), mars.orbit_data.mass.in_kg());
+        let momentum = add(scale(venus.vel[1], venus_mass), scale(mars.vel[1], mars_mass));
+        let centre = scale(add(scale(venus.coords[1], venus_mass), scale(mars.coords[1], mars_mass)), 1.0 / (venus_mass + mars_mass));
+        system.merge("Mars", "Venus").expect("both are in the system");
+
+        assert!(system.find("Mars").is_none(), "the lighter body should be absorbed");
+        let merged = system.find("Venus").expect("Venus survives");
+        let mass = merged.orbit_data.mass.in_kg();
+        assert_eq!(mass, venus_mass + mars_mass);
+        let kept = scale(*merged.vel.last().expect("Venus has states"), mass);
+        assert!(norm(sub(kept, momentum)) < 1E-12 * norm(momentum), "momentum {:?} became {:?}", momentum, kept);
+        assert!(norm(sub(*merged.coords.last().expect("Venus has states"), centre)) < 1E-6);
+        let volume = |radius: Length| radius.in_km().powi(3);
+        assert!((volume(merged.radius) - volume(venus.radius) - volume(mars.radius)).abs() < 1E-9 * volume(merged.radius));
+        assert!(system.merge("Venus", "Vulcan").is_none());
+    }
+}
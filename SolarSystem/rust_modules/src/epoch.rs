@@ -0,0 +1,76 @@
+use crate::units::{JulianDate, Time};
+
+/// The J2000 epoch, 2000 January 1 12:00
+pub const J2000: JulianDate = JulianDate::days(2451545.0);
+pub const SECONDS_PER_DAY: f64 = 86400.0;
+pub const DAYS_PER_JULIAN_CENTURY: f64 = 36525.0;
+
+/// Julian date of a Gregorian calendar date, the day may carry a fraction for the time of day
+pub fn julian_date(year: i32, month: u32, day: f64) -> JulianDate {
+    // January and February count as the 13th and 14th months of the year before
+    let (year, month) = if month <= 2 { (year - 1, month + 12) } else { (year, month) };
+    let century = (year as f64 / 100.0).floor();
+    let gregorian_correction = 2.0 - century + (century / 4.0).floor();
+    JulianDate::days(
+        (365.25 * (year as f64 + 4716.0)).floor() + (30.6001 * (month as f64 + 1.0)).floor() + day + gregorian_correction - 1524.5,
+    )
+}
+
+/// Gregorian calendar date (year, month, day with fraction) of a Julian date, the inverse of `julian_date`
+pub fn calendar_date(julian_date: JulianDate) -> (i32, u32, f64) {
+    let shifted = julian_date.in_days() + 0.5;
+    let whole = shifted.floor();
+    let fraction = shifted - whole;
+    let alpha = ((whole - 1867216.25) / 36524.25).floor();
+    let a = whole + 1.0 + alpha - (alpha / 4.0).floor();
+    let b = a + 1524.0;
+    let c = ((b - 122.1) / 365.25).floor();
+    let d = (365.25 * c).floor();
+    let e = ((b - d) / 30.6001).floor();
+    let day = b - d - (30.6001 * e).floor() + fraction;
+    let month = if e < 14.0 { e - 1.0 } else { e - 13.0 };
+    let year = if month > 2.0 { c - 4716.0 } else { c - 4715.0 };
+    (year as i32, month as u32, day)
+}
+
+/// Julian dates from `start` to `end` every `step`, the end included when the steps land on it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DateRange {
+    pub start: JulianDate,
+    pub end: JulianDate,
+    pub step: Time,
+}
+
+impl DateRange {
+    /// Every date in the range, empty if it ends before it starts or the step isn't positive
+    pub fn dates(&self) -> Vec<JulianDate> {
+        if self.step.in_seconds().is_nan() || self.step <= Time::default() {
+            return Vec::new();
+        }
+        let count = ((self.end - self.start) / self.step + 1E-9).floor().max(-1.0) as i64 + 1;
+        (0..count).map(|i| self.start + i as f64 * self.step).collect()
+    }
+}
+
+/// Julian centuries between J2000 and a Julian date
+pub fn centuries_since_j2000(julian_date: JulianDate) -> f64 {
+    (julian_date - J2000).in_days() / DAYS_PER_JULIAN_CENTURY
+}
+
+/// ISO 8601 style timestamp of a Julian date to the millisecond, e.g. 2000-01-01T12:00:00.000
+pub fn iso_timestamp(julian_date: JulianDate) -> String {
+    let julian_date = julian_date.in_days();
+    let mut midnight = (julian_date - 0.5).floor() + 0.5;
+    let mut milliseconds = ((julian_date - midnight) * SECONDS_PER_DAY * 1000.0).round() as u64;
+    // Rounding can carry into the next day
+    if milliseconds >= SECONDS_PER_DAY as u64 * 1000 {
+        midnight += 1.0;
+        milliseconds = 0;
+    }
+    let (year, month, day) = calendar_date(JulianDate::days(midnight));
+    let seconds = milliseconds / 1000;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+        year, month, day.round() as u32, seconds / 3600, (seconds / 60) % 60, seconds % 60, milliseconds % 1000
+    )
+}
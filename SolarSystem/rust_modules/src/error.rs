@@ -0,0 +1,134 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Where in the input a problem was found, as much of it as is known
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    pub path: Option<PathBuf>, // None when reading from a string
+    pub line: Option<u32>, // 1 based
+    pub column: Option<u32>, // 1 based
+}
+
+impl Location {
+    /// Location of a byte offset into the contents of a file
+    pub fn from_offset(path: Option<PathBuf>, contents: &str, offset: usize) -> Self {
+        let before = &contents[..offset.min(contents.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+        Self { path, line: Some(line as u32), column: Some(column as u32) }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}", path.display())?,
+            None => write!(f, "<input>")?,
+        }
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Everything that can go wrong loading a system of bodies
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be opened or read
+    Io { path: PathBuf, source: io::Error },
+    /// The text isn't valid TOML
+    Syntax { location: Location, message: String },
+    /// A required field is absent. body is None for fields at the top of the file
+    MissingField { body: Option<String>, field: String, location: Location },
+    /// A field is there but its value can't be used
+    InvalidField { body: Option<String>, field: String, reason: String, location: Location },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, source } => write!(f, "couldn't read {}: {}", path.display(), source),
+            LoadError::Syntax { location, message } => write!(f, "{}: invalid TOML: {}", location, message),
+            LoadError::MissingField { body: Some(body), field, location } => {
+                write!(f, "{}: {} is missing `{}`", location, body, field)
+            }
+            LoadError::MissingField { body: None, field, location } => write!(f, "{}: missing `{}`", location, field),
+            LoadError::InvalidField { body: Some(body), field, reason, location } => {
+                write!(f, "{}: {} has an invalid `{}`: {}", location, body, field, reason)
+            }
+            LoadError::InvalidField { body: None, field, reason, location } => {
+                write!(f, "{}: invalid `{}`: {}", location, field, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planet::SolarSystem;
+
+    const MERCURY: &str = "number_of_bodies = 1
+
+[SolarSystem.Mercury]
+semi_major_axis_km = 57909226.5
+eccentricity = 0.20563593
+inclination_degrees = 7.00497902
+mean_longitude_degrees = 252.2503235
+longitude_of_perihelion_degrees = 77.45779628
+longitude_of_the_ascending_node_degrees = 48.33076593
+meanradius_km = 2439.4
+mass_kg = 3.301E23
+";
+
+    fn load_error(contents: &str) -> LoadError {
+        match contents.parse::<SolarSystem>() {
+            Ok(_) => panic!("loaded without an error:\n{}", contents),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_io_error_naming_the_path() {
+        let error = SolarSystem::from_path("no/such/bodies.toml").err().expect("loaded a file that isn't there");
+        assert!(matches!(&error, LoadError::Io { path, .. } if path.ends_with("bodies.toml")), "{:?}", error);
+        assert!(error.to_string().contains("no/such/bodies.toml"), "{}", error);
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_line() {
+        let error = load_error(&MERCURY.replace("eccentricity = 0.20563593", "eccentricity = = 0.2"));
+        let LoadError::Syntax { location, .. } = &error else { panic!("expected a syntax error, got {:?}", error) };
+        assert_eq!(location.line, Some(5));
+    }
+
+    #[test]
+    fn missing_fields_name_the_body_and_field() {
+        let error = load_error(&MERCURY.replace("mass_kg = 3.301E23\n", ""));
+        let LoadError::MissingField { body, field, location } = &error else { panic!("expected a missing field, got {:?}", error) };
+        assert_eq!(body.as_deref(), Some("Mercury"));
+        assert_eq!(field, "mass_kg");
+        assert_eq!(location.line, Some(3));
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_invalid_fields() {
+        let error = load_error(&MERCURY.replace("meanradius_km = 2439.4", "meanradius_km = \"large\""));
+        let LoadError::InvalidField { body, field, location, .. } = &error else { panic!("expected an invalid field, got {:?}", error) };
+        assert_eq!(body.as_deref(), Some("Mercury"));
+        assert_eq!(field, "meanradius_km");
+        assert_eq!(location.line, Some(10));
+    }
+}
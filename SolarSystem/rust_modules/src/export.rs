@@ -0,0 +1,221 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::epoch::iso_timestamp;
+use crate::frames::Frame;
+use crate::planet::SolarSystem;
+use crate::units::{JulianDate, Position, Time, Velocity};
+
+/// Time scale the Julian dates are in
+const TIME_SYSTEM: &str = "TDB";
+/// The Unix epoch, for stamping OEM files with their creation date
+const UNIX_EPOCH_JD: JulianDate = JulianDate::days(2440587.5);
+
+/// One sample of an exported trajectory, the vectors only come out alongside its frame like those of a `State`
+#[derive(Serialize, Debug, Clone)]
+pub struct EphemerisPoint {
+    pub julian_date: JulianDate,
+    pub time: Time, // past the epoch
+    #[serde(skip)]
+    pub(crate) frame: Frame, // named once for the whole ephemeris
+    pub(crate) position: [f64; 3], // km
+    pub(crate) velocity: [f64; 3], // km/s
+}
+
+impl EphemerisPoint {
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// Position in `frame`, None if the point is in another one
+    pub fn position_in(&self, frame: &Frame) -> Option<Position> {
+        (*frame == self.frame).then_some(Position::km(self.position))
+    }
+
+    /// Velocity in `frame`, None if the point is in another one
+    pub fn velocity_in(&self, frame: &Frame) -> Option<Velocity> {
+        (*frame == self.frame).then_some(Velocity::km_per_s(self.velocity))
+    }
+}
+
+/// Trajectories of a set of bodies relative to one center, ready to be written out
+#[derive(Serialize, Debug, Clone)]
+pub struct Ephemeris {
+    pub frame: String,
+    pub center: String,
+    pub time_system: String,
+    pub epoch_jd: JulianDate,
+    pub units: BTreeMap<String, String>,
+    pub bodies: BTreeMap<String, Vec<EphemerisPoint>>,
+}
+
+impl Ephemeris {
+    /// Gathers the stored history of each named body in `frame`, see `State::to_frame`.
+    /// None if a name isn't in the system or a state can't be put in the frame
+    pub fn from_system(system: &SolarSystem, bodies: &[&str], frame: &Frame) -> Option<Self> {
+        let mut trajectories = BTreeMap::new();
+        for name in bodies {
+            let points = system
+                .heliocentric_states(name)?
+                .iter()
+                .map(|state| {
+                    let state = state.to_frame(system, frame)?;
+                    Some(EphemerisPoint {
+                        julian_date: system.epoch + state.time,
+                        time: state.time,
+                        frame: state.frame,
+                        position: state.position,
+                        velocity: state.velocity,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            trajectories.insert(name.to_string(), points);
+        }
+        let units = [("position", "km"), ("velocity", "km/s"), ("time", "Julian date (TDB)")]
+            .iter()
+            .map(|(quantity, unit)| (quantity.to_string(), unit.to_string()))
+            .collect();
+        let center = match frame {
+            Frame::Synodic { primary, secondary } => format!("{}-{} barycenter", primary, secondary),
+            _ => frame.center().unwrap_or_default().to_string(),
+        };
+        Some(Self {
+            // Frames SPICE doesn't know go by their own description
+            frame: frame.spice_name().unwrap_or_else(|| frame.to_string()),
+            center,
+            time_system: TIME_SYSTEM.to_string(),
+            epoch_jd: system.epoch,
+            units,
+            bodies: trajectories,
+        })
+    }
+
+    /// Comma separated values, one row per body per sample.
+    /// Lines starting with # before the column names give the frame, center and units
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "# frame: {}", self.frame)?;
+        writeln!(writer, "# center: {}", self.center)?;
+        writeln!(writer, "# time system: {}, epoch JD {}", self.time_system, self.epoch_jd.in_days())?;
+        writeln!(writer, "# units: position km, velocity km/s, seconds_since_epoch s")?;
+        writeln!(writer, "body,julian_date,seconds_since_epoch,x_km,y_km,z_km,vx_km_s,vy_km_s,vz_km_s")?;
+        for (name, points) in &self.bodies {
+            for point in points {
+                let [x, y, z] = point.position;
+                let [vx, vy, vz] = point.velocity;
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{},{},{},{}",
+                    name, point.julian_date.in_days(), point.time.in_seconds(), x, y, z, vx, vy, vz
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pretty printed JSON of the whole ephemeris, frame and units included
+    pub fn write_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *writer, self)?;
+        writeln!(writer)
+    }
+
+    /// CCSDS Orbit Ephemeris Message (502.0-B-2) in keyword = value notation, one segment per body.
+    /// Fails with `InvalidInput` before writing anything unless every point is in a frame with a CCSDS name
+    /// (see `Frame::ccsds_name`), so ecliptic ephemerides have to be gathered in the equatorial frame first
+    pub fn write_oem(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut reference_frames = BTreeMap::new();
+        for (name, points) in &self.bodies {
+            if let Some(first) = points.first() {
+                let reference_frame = first.frame.ccsds_name().filter(|_| points.iter().all(|point| point.frame == first.frame));
+                let reference_frame = reference_frame.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no CCSDS reference frame name", first.frame))
+                })?;
+                reference_frames.insert(name, reference_frame);
+            }
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        writeln!(writer, "CCSDS_OEM_VERS = 2.0")?;
+        writeln!(writer, "CREATION_DATE = {}", iso_timestamp(UNIX_EPOCH_JD + Time::seconds(now)))?;
+        writeln!(writer, "ORIGINATOR = solar_system")?;
+        for (name, points) in &self.bodies {
+            let (Some(first), Some(last)) = (points.first(), points.last()) else {
+                continue;
+            };
+            writeln!(writer)?;
+            writeln!(writer, "META_START")?;
+            writeln!(writer, "OBJECT_NAME = {}", name)?;
+            writeln!(writer, "OBJECT_ID = {}", name)?;
+            writeln!(writer, "CENTER_NAME = {}", self.center)?;
+            writeln!(writer, "REF_FRAME = {}", reference_frames[name])?;
+            writeln!(writer, "TIME_SYSTEM = {}", self.time_system)?;
+            writeln!(writer, "START_TIME = {}", iso_timestamp(first.julian_date))?;
+            writeln!(writer, "STOP_TIME = {}", iso_timestamp(last.julian_date))?;
+            writeln!(writer, "META_STOP")?;
+            writeln!(writer)?;
+            writeln!(writer, "COMMENT Positions in km, velocities in km/s")?;
+            for point in points {
+                let [x, y, z] = point.position;
+                let [vx, vy, vz] = point.velocity;
+                writeln!(
+                    writer,
+                    "{} {:.6} {:.6} {:.6} {:.9} {:.9} {:.9}",
+                    iso_timestamp(point.julian_date), x, y, z, vx, vy, vz
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planet::DEFAULT_DATA_PATH;
+
+    fn system() -> SolarSystem {
+        SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load")
+    }
+
+    fn written(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut buffer = Vec::new();
+        write(&mut buffer).expect("writing to memory shouldn't fail");
+        String::from_utf8(buffer).expect("output should be UTF-8")
+    }
+
+    #[test]
+    fn csv_header_names_the_frame_center_and_columns() {
+        let ephemeris = Ephemeris::from_system(&system(), &["Mars"], &Frame::ecliptic("Sun")).expect("Mars is in the system");
+        let csv = written(|buffer| ephemeris.write_csv(buffer));
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "# frame: ECLIPJ2000");
+        assert_eq!(lines[1], "# center: Sun");
+        assert_eq!(lines[2], "# time system: TDB, epoch JD 2451545");
+        assert_eq!(lines[4], "body,julian_date,seconds_since_epoch,x_km,y_km,z_km,vx_km_s,vy_km_s,vz_km_s");
+        assert!(lines[5].starts_with("Mars,2451545,0,"), "{}", lines[5]);
+    }
+
+    #[test]
+    fn oem_is_written_in_eme2000() {
+        let ephemeris = Ephemeris::from_system(&system(), &["Mars"], &Frame::EquatorialJ2000 { center: "Sun".to_string() })
+            .expect("Mars is in the system");
+        let oem = written(|buffer| ephemeris.write_oem(buffer));
+        for line in ["CCSDS_OEM_VERS = 2.0", "OBJECT_NAME = Mars", "CENTER_NAME = Sun", "REF_FRAME = EME2000", "TIME_SYSTEM = TDB"] {
+            assert!(oem.lines().any(|written| written == line), "no `{}` in\n{}", line, oem);
+        }
+        assert!(oem.contains("START_TIME = 2000-01-01T12:00:00.000"), "{}", oem);
+    }
+
+    #[test]
+    fn oem_refuses_frames_without_a_ccsds_name() {
+        let ephemeris = Ephemeris::from_system(&system(), &["Mars"], &Frame::ecliptic("Sun")).expect("Mars is in the system");
+        let mut buffer = Vec::new();
+        let error = ephemeris.write_oem(&mut buffer).expect_err("ecliptic J2000 has no CCSDS name");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(buffer.is_empty(), "nothing should be written before the check");
+        assert!(Ephemeris::from_system(&system(), &["Vulcan"], &Frame::ecliptic("Sun")).is_none());
+    }
+}
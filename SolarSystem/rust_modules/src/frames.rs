@@ -0,0 +1,367 @@
+use std::fmt;
+
+use crate::horizons::interpolate;
+use crate::planet::SolarSystem;
+use crate::schema::RotationRecord;
+use crate::units::{Angle, AngularRate, Position, Time, Velocity};
+use crate::vector::{add, cross, dot, norm, scale, sub};
+
+/// Obliquity of the ecliptic at J2000 between the ecliptic and equatorial frames, in arcseconds, as Horizons uses it
+pub const OBLIQUITY_J2000_ARCSECONDS: f64 = 84381.448;
+const SECONDS_PER_HOUR: f64 = 3600.0;
+
+/// A frame positions and velocities are given in: where its origin is and which way its axes point
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// Ecliptic and equinox of J2000 centred on a body, the frame the system's data and histories are kept in
+    EclipticJ2000 { center: String },
+    /// Earth's mean equator and equinox of J2000 centred on a body, which the ICRF matches to within milliarcseconds
+    EquatorialJ2000 { center: String },
+    /// Centred on a body with z along its spin axis and x along its equator's ascending node on the ecliptic, not turning
+    BodyInertial { body: String },
+    /// Centred on a body and turning with it, x through its prime meridian and z along its spin axis
+    BodyFixed { body: String },
+    /// Centred on the barycentre of two bodies and turning with them,
+    /// x from the primary to the secondary and z along their orbital angular momentum
+    Synodic { primary: String, secondary: String },
+}
+
+impl Frame {
+    /// The ecliptic J2000 frame centred on a body
+    pub fn ecliptic(center: &str) -> Self {
+        Frame::EclipticJ2000 { center: center.to_string() }
+    }
+
+    /// Name SPICE uses for the frame's axes: ECLIPJ2000, J2000 for the mean equator and equinox,
+    /// IAU_<BODY> for body fixed frames. None for frames SPICE has no built in name for
+    pub fn spice_name(&self) -> Option<String> {
+        match self {
+            Frame::EclipticJ2000 { .. } => Some("ECLIPJ2000".to_string()),
+            Frame::EquatorialJ2000 { .. } => Some("J2000".to_string()),
+            Frame::BodyFixed { body } => Some(format!("IAU_{}", body.to_uppercase())),
+            Frame::BodyInertial { .. } | Frame::Synodic { .. } => None,
+        }
+    }
+
+    /// Name CCSDS navigation messages such as OEM use for the frame's axes, EME2000 for the mean equator and equinox
+    /// of J2000. None for the others, which have no CCSDS reference frame name
+    pub fn ccsds_name(&self) -> Option<&'static str> {
+        match self {
+            Frame::EquatorialJ2000 { .. } => Some("EME2000"),
+            Frame::EclipticJ2000 { .. } | Frame::BodyInertial { .. } | Frame::BodyFixed { .. } | Frame::Synodic { .. } => None,
+        }
+    }
+
+    /// The body at the origin, None for synodic frames whose origin is a barycentre
+    pub fn center(&self) -> Option<&str> {
+        match self {
+            Frame::EclipticJ2000 { center } | Frame::EquatorialJ2000 { center } => Some(center),
+            Frame::BodyInertial { body } | Frame::BodyFixed { body } => Some(body),
+            Frame::Synodic { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Frame::EclipticJ2000 { center } => write!(f, "{} ecliptic J2000", center),
+            Frame::EquatorialJ2000 { center } => write!(f, "{} equatorial J2000", center),
+            Frame::BodyInertial { body } => write!(f, "{} inertial", body),
+            Frame::BodyFixed { body } => write!(f, "{} body fixed", body),
+            Frame::Synodic { primary, secondary } => write!(f, "{}-{} synodic", primary, secondary),
+        }
+    }
+}
+
+/// Position and velocity at a time, tagged with the frame they're in.
+/// The vectors only come out alongside the frame, see `to_frame` for getting them in another one
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub(crate) frame: Frame,
+    pub(crate) time: Time, // past the system epoch
+    pub(crate) position: [f64; 3], // km
+    pub(crate) velocity: [f64; 3], // km/s
+}
+
+/// How a body spins, for frames fixed to it
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    pub pole: [f64; 3], // unit vector along the spin axis in the ecliptic J2000 frame
+    pub prime_meridian: Angle, // from the equator's ascending node at the system epoch
+    pub rate: AngularRate, // negative for retrograde spin
+}
+
+impl Rotation {
+    /// From the data file, the pole defaults to the z axis of the system's frame
+    pub fn from_record(record: &RotationRecord) -> Self {
+        Self {
+            pole: pole_direction(record.pole_longitude_degrees, record.pole_latitude_degrees),
+            prime_meridian: Angle::degrees(record.prime_meridian_degrees.unwrap_or(0.0)),
+            rate: AngularRate::radians_per_s(std::f64::consts::TAU / (record.period_hours * SECONDS_PER_HOUR)),
+        }
+    }
+
+    /// Angle of the prime meridian from the equator's ascending node `time` past the epoch
+    pub fn angle(&self, time: Time) -> Angle {
+        self.prime_meridian + self.rate * time
+    }
+}
+
+/// Unit vector for a pole given in ecliptic longitude and latitude (degrees), the z axis when they're left out
+pub fn pole_direction(longitude_degrees: Option<f64>, latitude_degrees: Option<f64>) -> [f64; 3] {
+    let longitude = longitude_degrees.unwrap_or(0.0).to_radians();
+    let latitude = latitude_degrees.unwrap_or(90.0).to_radians();
+    [latitude.cos() * longitude.cos(), latitude.cos() * longitude.sin(), latitude.sin()]
+}
+
+/// Axes of a body's equatorial frame as rows: x along the equator's ascending node on the ecliptic, z along the pole
+pub fn equatorial_axes(pole: [f64; 3]) -> [[f64; 3]; 3] {
+    let node = cross([0.0, 0.0, 1.0], pole);
+    let x = if norm(node) < 1e-12 { [1.0, 0.0, 0.0] } else { scale(node, 1.0 / norm(node)) };
+    [x, cross(pole, x), pole]
+}
+
+/// Rotates a vector from the ecliptic of J2000 into Earth's mean equator and equinox of J2000 (ICRF)
+pub fn ecliptic_to_equatorial(vector: [f64; 3]) -> [f64; 3] {
+    let (sin, cos) = (OBLIQUITY_J2000_ARCSECONDS / 3600.0).to_radians().sin_cos();
+    [vector[0], cos * vector[1] - sin * vector[2], sin * vector[1] + cos * vector[2]]
+}
+
+/// The inverse of `ecliptic_to_equatorial`
+pub fn equatorial_to_ecliptic(vector: [f64; 3]) -> [f64; 3] {
+    let (sin, cos) = (OBLIQUITY_J2000_ARCSECONDS / 3600.0).to_radians().sin_cos();
+    [vector[0], cos * vector[1] + sin * vector[2], -sin * vector[1] + cos * vector[2]]
+}
+
+/// A frame at one moment, relative to the ecliptic J2000 frame centred on the central body
+struct Placement {
+    origin: ([f64; 3], [f64; 3]), // km and km/s
+    axes: [[f64; 3]; 3], // rows, the frame's axes in ecliptic coordinates
+    spin: [f64; 3], // radians/s, the frame's angular velocity in ecliptic coordinates
+}
+
+impl Placement {
+    /// A state relative to the central body's ecliptic frame, given in this frame
+    fn enter(&self, position: [f64; 3], velocity: [f64; 3]) -> ([f64; 3], [f64; 3]) {
+        let relative = sub(position, self.origin.0);
+        let moving = sub(sub(velocity, self.origin.1), cross(self.spin, relative));
+        (self.rotate(relative), self.rotate(moving))
+    }
+
+    /// A state given in this frame, relative to the central body's ecliptic frame
+    fn leave(&self, position: [f64; 3], velocity: [f64; 3]) -> ([f64; 3], [f64; 3]) {
+        let relative = self.unrotate(position);
+        let moving = add(self.unrotate(velocity), cross(self.spin, relative));
+        (add(self.origin.0, relative), add(self.origin.1, moving))
+    }
+
+    fn rotate(&self, vector: [f64; 3]) -> [f64; 3] {
+        self.axes.map(|axis| dot(axis, vector))
+    }
+
+    fn unrotate(&self, vector: [f64; 3]) -> [f64; 3] {
+        let [x, y, z] = self.axes;
+        add(add(scale(x, vector[0]), scale(y, vector[1])), scale(z, vector[2]))
+    }
+}
+
+const ECLIPTIC_AXES: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+impl SolarSystem {
+    /// Heliocentric ecliptic state of a body (or the central body) `time` past the epoch, interpolated from the
+    /// stored history where it covers the time and from the elements otherwise (see `heliocentric_ephemeris`)
+    pub fn heliocentric_state_at(&self, name: &str, time: Time) -> Option<State> {
+        if name == self.central_body.name {
+            return Some(State { frame: Frame::ecliptic(name), time, position: [0.0; 3], velocity: [0.0; 3] });
+        }
+        let body = self.find(name)?;
+        let history: Vec<([f64; 3], [f64; 3])> =
+            self.heliocentric_states(name)?.into_iter().map(|state| (state.position, state.velocity)).collect();
+        match interpolate(&body.times[..history.len()], &history, time) {
+            Some((position, velocity)) => Some(State { frame: Frame::ecliptic(&self.central_body.name), time, position, velocity }),
+            None => self.heliocentric_ephemeris(name, time),
+        }
+    }
+
+    /// Pole (unit vector in ecliptic J2000) of a body from its rotation, or failing that its zonal harmonics
+    fn pole_of(&self, name: &str) -> Option<[f64; 3]> {
+        let body = self.find(name)?;
+        body.rotation.as_ref().map(|rotation| rotation.pole).or_else(|| body.zonal_harmonics.as_ref().map(|harmonics| harmonics.pole))
+    }
+
+    /// Where a frame is `time` past the epoch. None if a body it refers to isn't in the system or lacks the
+    /// pole or rotation it needs
+    fn placement(&self, frame: &Frame, time: Time) -> Option<Placement> {
+        let origin = |name: &str| self.heliocentric_state_at(name, time).map(|state| (state.position, state.velocity));
+        Some(match frame {
+            Frame::EclipticJ2000 { center } => Placement { origin: origin(center)?, axes: ECLIPTIC_AXES, spin: [0.0; 3] },
+            Frame::EquatorialJ2000 { center } => {
+                let (sin, cos) = (OBLIQUITY_J2000_ARCSECONDS / 3600.0).to_radians().sin_cos();
+                Placement { origin: origin(center)?, axes: [[1.0, 0.0, 0.0], [0.0, cos, -sin], [0.0, sin, cos]], spin: [0.0; 3] }
+            }
+            Frame::BodyInertial { body } => Placement { origin: origin(body)?, axes: equatorial_axes(self.pole_of(body)?), spin: [0.0; 3] },
+            Frame::BodyFixed { body } => {
+                let rotation = self.find(body)?.rotation.as_ref()?;
+                let [node, ninety, pole] = equatorial_axes(rotation.pole);
+                let (sin, cos) = rotation.angle(time).sin_cos();
+                Placement {
+                    origin: origin(body)?,
+                    axes: [add(scale(node, cos), scale(ninety, sin)), sub(scale(ninety, cos), scale(node, sin)), pole],
+                    spin: scale(pole, rotation.rate.in_radians_per_s()),
+                }
+            }
+            Frame::Synodic { primary, secondary } => {
+                let mass = |name: &str| {
+                    if name == self.central_body.name { Some(self.central_body.mass) } else { self.find(name).map(|body| body.orbit_data.mass) }
+                };
+                let (primary_mass, secondary_mass) = (mass(primary)?, mass(secondary)?);
+                let ((primary_position, primary_velocity), (secondary_position, secondary_velocity)) = (origin(primary)?, origin(secondary)?);
+                let total = primary_mass + secondary_mass;
+                let weigh = |a: [f64; 3], b: [f64; 3]| add(scale(a, primary_mass / total), scale(b, secondary_mass / total));
+                let separation = sub(secondary_position, primary_position);
+                let momentum = cross(separation, sub(secondary_velocity, primary_velocity));
+                let x = scale(separation, 1.0 / norm(separation));
+                let z = scale(momentum, 1.0 / norm(momentum));
+                Placement {
+                    origin: (weigh(primary_position, secondary_position), weigh(primary_velocity, secondary_velocity)),
+                    axes: [x, cross(z, x), z],
+                    spin: scale(momentum, 1.0 / dot(separation, separation)),
+                }
+            }
+        })
+    }
+}
+
+impl State {
+    /// A position and velocity given in `frame`, `time` past the system epoch
+    pub fn new(frame: Frame, time: Time, position: Position, velocity: Velocity) -> Self {
+        Self { frame, time, position: position.in_km(), velocity: velocity.in_km_per_s() }
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// Past the system epoch
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    /// Position in `frame`, None if the state is in another one
+    pub fn position_in(&self, frame: &Frame) -> Option<Position> {
+        (*frame == self.frame).then_some(Position::km(self.position))
+    }
+
+    /// Velocity in `frame`, None if the state is in another one
+    pub fn velocity_in(&self, frame: &Frame) -> Option<Velocity> {
+        (*frame == self.frame).then_some(Velocity::km_per_s(self.velocity))
+    }
+
+    /// Position and velocity as a pair, for code that works within a single frame
+    pub(crate) fn vectors(&self) -> ([f64; 3], [f64; 3]) {
+        (self.position, self.velocity)
+    }
+
+    /// The same state in another frame at the same moment. Frames centred on bodies are placed from their stored
+    /// histories where those cover the time and from their elements otherwise (see `SolarSystem::heliocentric_state_at`).
+    /// None if a body either frame refers to isn't in the system, or a body frame's body has no pole
+    /// (from its rotation or zonal harmonics) or for body fixed frames no rotation
+    pub fn to_frame(&self, system: &SolarSystem, frame: &Frame) -> Option<State> {
+        if *frame == self.frame {
+            return Some(self.clone());
+        }
+        let (position, velocity) = system.placement(&self.frame, self.time)?.leave(self.position, self.velocity);
+        let (position, velocity) = system.placement(frame, self.time)?.enter(position, velocity);
+        Some(State { frame: frame.clone(), time: self.time, position, velocity })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planet::DEFAULT_DATA_PATH;
+
+    /// The shipped system with the Earth spinning once a sidereal day about a pole tilted by the obliquity
+    fn system() -> SolarSystem {
+        let mut system = SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load");
+        let record = RotationRecord {
+            period_hours: 23.9345,
+            prime_meridian_degrees: Some(30.0),
+            pole_longitude_degrees: Some(90.0),
+            pole_latitude_degrees: Some(90.0 - OBLIQUITY_J2000_ARCSECONDS / 3600.0),
+        };
+        system.bodies.get_mut("Earth").expect("Earth is in the system").rotation = Some(Rotation::from_record(&record));
+        system
+    }
+
+    fn mars(system: &SolarSystem) -> State {
+        system.heliocentric_state_at("Mars", Time::days(10.0)).expect("Mars is in the system")
+    }
+
+    /// Going through heliocentric coordinates some 1e8 km out costs about 1e-8 km in rounding
+    fn assert_same(found: &State, expected: &State) {
+        assert_eq!(found.frame, expected.frame);
+        let (position_error, velocity_error) = (norm(sub(found.position, expected.position)), norm(sub(found.velocity, expected.velocity)));
+        assert!(position_error < 1E-6 && velocity_error < 1E-9, "{:?} came back as {:?}", expected, found);
+    }
+
+    #[test]
+    fn obliquity_rotation_round_trips() {
+        let vector = [1.0, -2.0, 3.0];
+        assert!(norm(sub(equatorial_to_ecliptic(ecliptic_to_equatorial(vector)), vector)) < 1E-15);
+        // The ecliptic pole leans away from the celestial pole towards -y by the obliquity
+        let (sin, cos) = (OBLIQUITY_J2000_ARCSECONDS / 3600.0).to_radians().sin_cos();
+        assert!(norm(sub(ecliptic_to_equatorial([0.0, 0.0, 1.0]), [0.0, -sin, cos])) < 1E-15);
+        assert!(norm(sub(ecliptic_to_equatorial([0.0, 1.0, 0.0]), [0.0, cos, sin])) < 1E-15);
+    }
+
+    #[test]
+    fn states_come_back_from_every_kind_of_frame() {
+        let system = system();
+        let start = mars(&system);
+        let frames = [
+            Frame::EquatorialJ2000 { center: "Earth".to_string() },
+            Frame::BodyInertial { body: "Earth".to_string() },
+            Frame::BodyFixed { body: "Earth".to_string() },
+            Frame::Synodic { primary: "Sun".to_string(), secondary: "Earth".to_string() },
+            Frame::ecliptic("Jupiter"),
+        ];
+        for frame in &frames {
+            let there = start.to_frame(&system, frame).expect("every frame can be placed");
+            assert_eq!(there.frame, *frame);
+            assert_same(&there.to_frame(&system, &start.frame).expect("and left again"), &start);
+        }
+        // Through all of them in turn
+        let end = frames.iter().fold(start.clone(), |state, frame| state.to_frame(&system, frame).expect("every frame can be placed"));
+        assert_same(&end.to_frame(&system, &start.frame).expect("and left again"), &start);
+    }
+
+    #[test]
+    fn body_fixed_and_synodic_frames_turn() {
+        let system = system();
+        // A point on the equator at rest on the surface moves at the equatorial speed in the inertial frame
+        let (fixed, position) = (Frame::BodyFixed { body: "Earth".to_string() }, Position::km([6378.0, 0.0, 0.0]));
+        let surface = State::new(fixed, Time::days(1.0), position, Velocity::default());
+        let inertial = surface.to_frame(&system, &Frame::BodyInertial { body: "Earth".to_string() }).expect("Earth has a pole");
+        let expected = 6378.0 * std::f64::consts::TAU / (23.9345 * SECONDS_PER_HOUR);
+        assert!((norm(inertial.velocity) - expected).abs() < 1E-9, "{} km/s", norm(inertial.velocity));
+        assert!(inertial.velocity[2].abs() < 1E-12 && inertial.position[2].abs() < 1E-6, "{:?}", inertial);
+
+        // The Earth sits on the synodic x axis, moving only along it
+        let earth = system.heliocentric_state_at("Earth", Time::days(10.0)).expect("Earth is in the system");
+        let frame = Frame::Synodic { primary: "Sun".to_string(), secondary: "Earth".to_string() };
+        let synodic = earth.to_frame(&system, &frame).expect("both are in the system");
+        assert!(synodic.position[0] > 1.4E8 && synodic.position[1].abs() < 1E-6 && synodic.position[2].abs() < 1E-6, "{:?}", synodic.position);
+        assert!(synodic.velocity[1].abs() < 1E-9 && synodic.velocity[2].abs() < 1E-9, "{:?}", synodic.velocity);
+    }
+
+    #[test]
+    fn frames_that_cannot_be_placed_give_nothing() {
+        let system = system();
+        let start = mars(&system);
+        assert!(start.to_frame(&system, &Frame::ecliptic("Vulcan")).is_none());
+        assert!(start.to_frame(&system, &Frame::BodyFixed { body: "Mars".to_string() }).is_none(), "Mars has no rotation");
+        assert!(start.to_frame(&system, &Frame::BodyInertial { body: "Venus".to_string() }).is_none(), "Venus has no pole");
+    }
+}
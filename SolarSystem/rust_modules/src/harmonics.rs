@@ -0,0 +1,179 @@
+use crate::frames::{equatorial_axes, pole_direction};
+use crate::planet::OrbitalElements;
+use crate::schema::ZonalHarmonicsRecord;
+use crate::units::{Acceleration, AngularRate, GravitationalParameter, Length, Position, Time};
+use crate::vector::{add, dot, norm, scale};
+
+/// Zonal harmonics of a body's gravity field, felt by whatever orbits it.
+/// The field is symmetric about the body's spin axis, `pole`
+#[derive(Debug, Clone)]
+pub struct ZonalHarmonics {
+    pub reference_radius: Length,
+    pub j2: f64, // none
+    pub j3: f64, // none, 0 when not given
+    pub j4: f64, // none, 0 when not given
+    pub pole: [f64; 3], // unit vector along the spin axis in the system's frame
+}
+
+impl ZonalHarmonics {
+    /// From the data file, the pole defaults to the z axis of the system's frame
+    pub fn from_record(record: &ZonalHarmonicsRecord) -> Self {
+        Self {
+            reference_radius: Length::km(record.reference_radius_km),
+            j2: record.j2,
+            j3: record.j3.unwrap_or(0.0),
+            j4: record.j4.unwrap_or(0.0),
+            pole: pole_direction(record.pole_longitude_degrees, record.pole_latitude_degrees),
+        }
+    }
+
+    /// A vector in the system's frame expressed in the body's equatorial frame, where the pole is the z axis
+    pub fn to_equatorial(&self, vector: [f64; 3]) -> [f64; 3] {
+        equatorial_axes(self.pole).map(|axis| dot(vector, axis))
+    }
+
+    /// The inverse of `to_equatorial`
+    pub fn from_equatorial(&self, vector: [f64; 3]) -> [f64; 3] {
+        let [x, y, z] = equatorial_axes(self.pole);
+        add(add(scale(x, vector[0]), scale(y, vector[1])), scale(z, vector[2]))
+    }
+
+    /// Acceleration from the J2, J3 and J4 terms on something at `position` from the body's centre,
+    /// mu being the body's own gravitational parameter. The point mass term isn't included.
+    /// Each term is the gradient of -mu Jn R^n Pn(sin latitude) / r^(n+1)
+    pub fn acceleration(&self, mu: GravitationalParameter, position: Position) -> Acceleration {
+        let (mu, reference_radius) = (mu.in_km3_per_s2(), self.reference_radius.in_km());
+        let position = position.in_km();
+        let r = norm(position);
+        let radial = scale(position, 1.0 / r);
+        // Sine of the latitude above the equator
+        let u = dot(radial, self.pole);
+        let (u2, u3, u4) = (u * u, u * u * u, u * u * u * u);
+        // (n, Jn, Pn(u), Pn'(u))
+        let terms = [
+            (2, self.j2, 0.5 * (3.0 * u2 - 1.0), 3.0 * u),
+            (3, self.j3, 0.5 * (5.0 * u3 - 3.0 * u), 0.5 * (15.0 * u2 - 3.0)),
+            (4, self.j4, 0.125 * (35.0 * u4 - 30.0 * u2 + 3.0), 0.5 * (35.0 * u3 - 15.0 * u)),
+        ];
+        let mut acceleration = [0.0; 3];
+        for (n, j, legendre, derivative) in terms {
+            if j == 0.0 {
+                continue;
+            }
+            let factor = mu * j * reference_radius.powi(n) / r.powi(n + 2);
+            let along_radius = (n + 1) as f64 * legendre + u * derivative;
+            acceleration = add(acceleration, scale(add(scale(radial, along_radius), scale(self.pole, -derivative)), factor));
+        }
+        Acceleration::km_per_s2(acceleration)
+    }
+
+    /// First order secular drift of the ascending node, argument of perigee and mean anomaly
+    /// (on top of the mean motion) from J2, for elements referred to this body's equator.
+    /// J3 has no first order secular effect and J4's is of order J2^2, so neither is included.
+    /// Hyperbolic orbits don't drift
+    pub fn secular_rates(&self, elements: &OrbitalElements, mu: GravitationalParameter) -> (AngularRate, AngularRate, AngularRate) {
+        let e = elements.eccentricity;
+        if e >= 1.0 {
+            return (AngularRate::default(), AngularRate::default(), AngularRate::default());
+        }
+        let a = elements.semimajor_axis;
+        let mean_motion = (mu.in_km3_per_s2() / a.in_km().powi(3)).sqrt();
+        let semi_latus_rectum = a * (1.0 - e * e);
+        let factor = mean_motion * self.j2 * (self.reference_radius / semi_latus_rectum).powi(2);
+        let cos_i = elements.inclination.cos();
+        (
+            AngularRate::radians_per_s(-1.5 * factor * cos_i),
+            AngularRate::radians_per_s(0.75 * factor * (5.0 * cos_i * cos_i - 1.0)),
+            AngularRate::radians_per_s(0.75 * factor * (1.0 - e * e).sqrt() * (3.0 * cos_i * cos_i - 1.0)),
+        )
+    }
+
+    /// Elements (in the system's frame) moved on by `time` along their orbit with the J2 secular drift added,
+    /// mu being the gravitational parameter of this body and the orbiting one together
+    pub fn advance(&self, elements: &OrbitalElements, mu: GravitationalParameter, time: Time) -> OrbitalElements {
+        let (position, velocity) = elements.to_state_vectors(mu);
+        let equatorial = OrbitalElements::from_state_vectors(self.to_equatorial(position), self.to_equatorial(velocity), mu, elements.mass);
+        let (node_rate, perigee_rate, anomaly_rate) = self.secular_rates(&equatorial, mu);
+        let mut moved = equatorial.after(mu, time);
+        moved.longitude_of_ascending_node = (moved.longitude_of_ascending_node + node_rate * time).wrapped();
+        moved.argument_of_parigee = (moved.argument_of_parigee + perigee_rate * time).wrapped();
+        moved.mean_anomoly += anomaly_rate * time;
+        if moved.eccentricity < 1.0 {
+            moved.mean_anomoly = moved.mean_anomoly.wrapped();
+        }
+        let (position, velocity) = moved.to_state_vectors(mu);
+        OrbitalElements::from_state_vectors(self.from_equatorial(position), self.from_equatorial(velocity), mu, elements.mass)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+    use crate::horizons::slope;
+    use crate::integrators::{Integrator, RungeKutta4};
+
+    const MU: GravitationalParameter = GravitationalParameter::km3_per_s2(398600.4418);
+
+    fn earth() -> ZonalHarmonics {
+        ZonalHarmonics { reference_radius: Length::km(6378.137), j2: 1.08263E-3, j3: 0.0, j4: 0.0, pole: [0.0, 0.0, 1.0] }
+    }
+
+    #[test]
+    fn secular_rates_match_a_numerical_run() {
+        let field = earth();
+        let start = OrbitalElements::new([7000.0, 0.01, 51.6, 30.0, 60.0, 0.0, 1000.0]);
+        let (node_rate, perigee_rate, _) = field.secular_rates(&start, MU);
+        // Worked by hand: n J2 (R/p)^2 is 9.69E-7 rad/s here
+        assert!((node_rate.in_degrees_per_day() + 4.47).abs() < 0.01, "node rate {} degrees a day", node_rate.in_degrees_per_day());
+        assert!((perigee_rate.in_degrees_per_day() - 3.34).abs() < 0.01, "perigee rate {} degrees a day", perigee_rate.in_degrees_per_day());
+
+        let (position, velocity) = start.to_state_vectors(MU);
+        let (mut positions, mut velocities) = ([position], [velocity]);
+        let gravity = |_time: Time, positions: &[[f64; 3]], _velocities: &[[f64; 3]]| -> Vec<[f64; 3]> {
+            let position = positions[0];
+            let point_mass = scale(position, -MU.in_km3_per_s2() / norm(position).powi(3));
+            vec![add(point_mass, field.acceleration(MU, Position::km(position)).in_km_per_s2())]
+        };
+        // Osculating node and perigee (radians, unwrapped) once a minute over two days, about thirty orbits,
+        // so a straight line through them averages out the short period terms
+        let (mut nodes, mut perigees): (Vec<(f64, f64)>, _) = (Vec::new(), Vec::new());
+        let mut integrator = RungeKutta4::new(Time::seconds(10.0));
+        let mut time = Time::default();
+        while time < Time::days(2.0) {
+            let elements = OrbitalElements::from_state_vectors(positions[0], velocities[0], MU, start.mass);
+            for (samples, angle) in [(&mut nodes, elements.longitude_of_ascending_node), (&mut perigees, elements.argument_of_parigee)] {
+                let mut angle = angle.in_radians();
+                if let Some(&(_, previous)) = samples.last() {
+                    angle += ((previous - angle) / TAU).round() * TAU;
+                }
+                samples.push((time.in_seconds(), angle));
+            }
+            let target = time + Time::seconds(60.0);
+            while time < target {
+                time += integrator.step(time, target - time, &mut positions, &mut velocities, &gravity);
+            }
+        }
+        for (name, samples, rate) in [("node", &nodes, node_rate), ("perigee", &perigees, perigee_rate)] {
+            let numerical = AngularRate::radians_per_s(slope(samples));
+            assert!(
+                ((numerical - rate) / rate).abs() < 0.02,
+                "{} drifts {} degrees a day numerically against {} from J2",
+                name,
+                numerical.in_degrees_per_day(),
+                rate.in_degrees_per_day()
+            );
+        }
+    }
+
+    #[test]
+    fn critical_inclination_and_hyperbolas_have_no_perigee_drift() {
+        let field = earth();
+        let critical = OrbitalElements::new([26600.0, 0.74, 63.4349, 0.0, 270.0, 0.0, 1000.0]);
+        let (_, perigee_rate, _) = field.secular_rates(&critical, MU);
+        assert!(perigee_rate.in_degrees_per_day().abs() < 1E-5, "perigee drifts {} degrees a day", perigee_rate.in_degrees_per_day());
+        let hyperbola = OrbitalElements::new([-20000.0, 1.5, 30.0, 0.0, 0.0, 0.0, 1000.0]);
+        assert_eq!(field.secular_rates(&hyperbola, MU), (AngularRate::default(), AngularRate::default(), AngularRate::default()));
+    }
+}
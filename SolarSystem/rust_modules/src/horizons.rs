@@ -0,0 +1,429 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use crate::epoch::SECONDS_PER_DAY;
+use crate::error::{LoadError, Location};
+use crate::frames::{equatorial_to_ecliptic, Frame, State};
+use crate::planet::SolarSystem;
+use crate::units::{JulianDate, Length, Position, Speed, Time, Velocity, KM_PER_AU};
+use crate::vector::{add, norm, scale, sub};
+
+/// One row of a Horizons vector table, converted to km, km/s and the ecliptic of J2000 centred on the table's center.
+/// The vectors only come out alongside the frame, like those of a `State`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HorizonsState {
+    pub julian_date: JulianDate, // TDB
+    pub(crate) frame: Frame,
+    pub(crate) position: [f64; 3], // km
+    pub(crate) velocity: [f64; 3], // km/s
+}
+
+impl HorizonsState {
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// Position in `frame`, None if the row is in another one
+    pub fn position_in(&self, frame: &Frame) -> Option<Position> {
+        (*frame == self.frame).then_some(Position::km(self.position))
+    }
+
+    /// Velocity in `frame`, None if the row is in another one
+    pub fn velocity_in(&self, frame: &Frame) -> Option<Velocity> {
+        (*frame == self.frame).then_some(Velocity::km_per_s(self.velocity))
+    }
+}
+
+/// A vector table saved from JPL Horizons, either the default layout or CSV_FORMAT=YES,
+/// in any of the KM-S, KM-D or AU-D output units and in the ecliptic or ICRF frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct HorizonsTable {
+    pub target: Option<String>, // from "Target body name: Earth (399)"
+    pub center: String, // from "Center body name: Sun (10)", the states are in its ecliptic frame
+    pub states: Vec<HorizonsState>,
+}
+
+/// Scale factors from a table's output units to km and km/s, None for units Horizons vector tables don't come in
+fn unit_scales(units: &str) -> Option<(f64, f64)> {
+    match units {
+        "AU-D" => Some((KM_PER_AU, KM_PER_AU / SECONDS_PER_DAY)),
+        "KM-D" => Some((1.0, 1.0 / SECONDS_PER_DAY)),
+        "KM-S" => Some((1.0, 1.0)),
+        _ => None,
+    }
+}
+
+/// The name in a header value such as "Earth (399)    {source: DE441}"
+fn header_name(value: &str) -> String {
+    value.split(['(', '{']).next().unwrap_or("").trim().to_string()
+}
+
+/// A state of the default layout while its lines are being read, which spans a date line and the lines under it
+struct PendingState {
+    line: usize, // 0 based index of the date line
+    julian_date: JulianDate,
+    values: Vec<(String, String)>,
+}
+
+/// Values of the KEY=value pairs on a line of the default layout, e.g. " X =-2.6E+07 Y = 1.3E+08"
+fn key_values(line: &str) -> Vec<(String, String)> {
+    let spaced = line.replace('=', " = ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+    tokens
+        .windows(3)
+        .filter(|window| window[1] == "=" && window[0] != "=" && window[2] != "=")
+        .map(|window| (window[0].to_string(), window[2].to_string()))
+        .collect()
+}
+
+impl HorizonsTable {
+    /// Reads a vector table saved to disk
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| LoadError::Io { path: path.to_path_buf(), source })?;
+        Self::parse(&contents, Some(path))
+    }
+
+    /// Reads the header for the names, units and frame, then every state between $$SOE and $$EOE.
+    /// path is only used to say where errors are
+    fn parse(contents: &str, path: Option<&Path>) -> Result<Self, LoadError> {
+        let location = |line: Option<u32>| Location { path: path.map(Path::to_path_buf), line, column: None };
+        let mut target = None;
+        let mut center = None;
+        // Tables without the header are in KM-S, the Horizons default
+        let mut units = (String::from("KM-S"), None);
+        let mut equatorial = false;
+        let mut data_start = None;
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim() == "$$SOE" {
+                data_start = Some(index + 1);
+                break;
+            }
+            let Some((key, value)) = line.split_once(':') else { continue };
+            match key.trim() {
+                "Target body name" => target = Some(header_name(value)),
+                "Center body name" => center = Some(header_name(value)),
+                "Output units" => units = (value.split(',').next().unwrap_or("").trim().to_string(), Some(index as u32 + 1)),
+                "Reference frame" => equatorial = value.contains("ICRF") || value.to_lowercase().contains("equator"),
+                _ => {}
+            }
+        }
+        let data_start = data_start.ok_or_else(|| LoadError::MissingField {
+            body: target.clone(),
+            field: "$$SOE".to_string(),
+            location: location(None),
+        })?;
+        let center = center.ok_or_else(|| LoadError::MissingField {
+            body: target.clone(),
+            field: "Center body name".to_string(),
+            location: location(None),
+        })?;
+        let reference_frame = Frame::ecliptic(&center);
+        let (position_scale, velocity_scale) = unit_scales(&units.0).ok_or_else(|| LoadError::InvalidField {
+            body: target.clone(),
+            field: "Output units".to_string(),
+            reason: format!("expected KM-S, KM-D or AU-D, found {}", units.0),
+            location: location(units.1),
+        })?;
+        let frame = |vector: [f64; 3]| if equatorial { equatorial_to_ecliptic(vector) } else { vector };
+
+        let number = |text: &str, field: &str, line: usize| {
+            text.trim().parse::<f64>().map_err(|_| LoadError::InvalidField {
+                body: target.clone(),
+                field: field.to_string(),
+                reason: format!("expected a number, found {}", text.trim()),
+                location: location(Some(line as u32 + 1)),
+            })
+        };
+        let mut states = Vec::new();
+        // The state being built in the default layout
+        let mut pending: Option<PendingState> = None;
+        let finish = |pending: Option<PendingState>, states: &mut Vec<HorizonsState>| -> Result<(), LoadError> {
+            let Some(PendingState { line, julian_date, values }) = pending else { return Ok(()) };
+            let mut components = [0.0; 6];
+            for (component, key) in components.iter_mut().zip(["X", "Y", "Z", "VX", "VY", "VZ"]) {
+                let value = values.iter().find(|(name, _)| name == key).ok_or_else(|| LoadError::MissingField {
+                    body: target.clone(),
+                    field: key.to_string(),
+                    location: location(Some(line as u32 + 1)),
+                })?;
+                *component = number(&value.1, key, line)?;
+            }
+            states.push(HorizonsState {
+                julian_date,
+                frame: reference_frame.clone(),
+                position: frame(scale([components[0], components[1], components[2]], position_scale)),
+                velocity: frame(scale([components[3], components[4], components[5]], velocity_scale)),
+            });
+            Ok(())
+        };
+        for (index, line) in contents.lines().enumerate().skip(data_start) {
+            let text = line.trim();
+            if text == "$$EOE" {
+                break;
+            }
+            if text.is_empty() {
+                continue;
+            }
+            if text.contains(',') {
+                // CSV layout: JDTDB, Calendar Date, X, Y, Z, VX, VY, VZ, then optional LT, RG, RR
+                let fields: Vec<&str> = text.split(',').collect();
+                if fields.len() < 8 {
+                    return Err(LoadError::MissingField {
+                        body: target.clone(),
+                        field: "VZ".to_string(),
+                        location: location(Some(index as u32 + 1)),
+                    });
+                }
+                let mut components = [0.0; 6];
+                for ((component, field), key) in components.iter_mut().zip(&fields[2..8]).zip(["X", "Y", "Z", "VX", "VY", "VZ"]) {
+                    *component = number(field, key, index)?;
+                }
+                states.push(HorizonsState {
+                    julian_date: JulianDate::days(number(fields[0], "JDTDB", index)?),
+                    frame: reference_frame.clone(),
+                    position: frame(scale([components[0], components[1], components[2]], position_scale)),
+                    velocity: frame(scale([components[3], components[4], components[5]], velocity_scale)),
+                });
+            }
+            else if let Some((date, _)) = text.split_once('=').filter(|(date, _)| date.trim().parse::<f64>().is_ok()) {
+                // A date line "2451545.000000000 = A.D. 2000-Jan-01 12:00:00.0000 TDB" starts the next state
+                finish(pending.take(), &mut states)?;
+                pending = Some(PendingState { line: index, julian_date: JulianDate::days(number(date, "JDTDB", index)?), values: Vec::new() });
+            }
+            else if let Some(state) = pending.as_mut() {
+                state.values.extend(key_values(text));
+            }
+        }
+        finish(pending, &mut states)?;
+        Ok(Self { target, center, states })
+    }
+
+    /// Compares the system's stored trajectory with this table, using the target and center named in its header.
+    /// A center of "Sun" means the system's central body, and the Earth-Moon barycenter is the data file's Earth
+    pub fn compare(&self, system: &SolarSystem) -> Option<ComparisonReport> {
+        let target = match self.target.as_deref()? {
+            "Earth-Moon Barycenter" => "Earth",
+            other => other,
+        };
+        let center = match self.center.as_str() {
+            "Sun" => system.central_body.name.as_str(),
+            other => other,
+        };
+        compare(system, self, target, center)
+    }
+}
+
+impl FromStr for HorizonsTable {
+    type Err = LoadError;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        Self::parse(contents, None)
+    }
+}
+
+/// Difference between the propagated and reference state at one epoch
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorSample {
+    pub julian_date: JulianDate,
+    pub position_error: Length,
+    pub velocity_error: Speed,
+}
+
+/// How far a propagated trajectory is from a Horizons table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    pub body: String,
+    pub center: String,
+    pub samples: Vec<ErrorSample>,
+    pub skipped: usize, // table epochs outside the propagated span
+    pub max_position_error: Length,
+    pub rms_position_error: Length,
+    pub max_velocity_error: Speed,
+    pub rms_velocity_error: Speed,
+    pub position_error_growth: Length, // per day, slope of a least squares line through the errors
+    pub velocity_error_growth: Speed, // per day
+}
+
+/// State at `time` (past the system epoch) from the stored samples around it, by cubic Hermite interpolation
+/// on the positions and velocities. None outside the stored span
+pub(crate) fn interpolate(times: &[Time], states: &[([f64; 3], [f64; 3])], time: Time) -> Option<([f64; 3], [f64; 3])> {
+    let (first, last) = (*times.first()?, *times.last()?);
+    if time < first || time > last {
+        return None;
+    }
+    let after = times.partition_point(|sample| *sample < time).max(1).min(times.len() - 1);
+    if times.len() == 1 || times[after] == time {
+        return Some(states[after]);
+    }
+    let before = after - 1;
+    let (p0, v0) = states[before];
+    let (p1, v1) = states[after];
+    let h = (times[after] - times[before]).in_seconds();
+    let s = (time - times[before]).in_seconds() / h;
+    let (s2, s3) = (s * s, s * s * s);
+    let position = add(
+        add(scale(p0, 2.0 * s3 - 3.0 * s2 + 1.0), scale(v0, h * (s3 - 2.0 * s2 + s))),
+        add(scale(p1, -2.0 * s3 + 3.0 * s2), scale(v1, h * (s3 - s2))),
+    );
+    let velocity = add(
+        add(scale(p0, (6.0 * s2 - 6.0 * s) / h), scale(v0, 3.0 * s2 - 4.0 * s + 1.0)),
+        add(scale(p1, (-6.0 * s2 + 6.0 * s) / h), scale(v1, 3.0 * s2 - 2.0 * s)),
+    );
+    Some((position, velocity))
+}
+
+/// Slope of the least squares line through (x, y) points
+pub(crate) fn slope(points: &[(f64, f64)]) -> f64 {
+    let count = points.len() as f64;
+    if points.len() < 2 {
+        return 0.0;
+    }
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if variance == 0.0 { 0.0 } else { covariance / variance }
+}
+
+/// Compares the stored trajectory of `body` relative to `center` (any body or the central body) with a table.
+/// Table epochs between stored samples are interpolated, ones outside the propagated span are skipped.
+/// None if either name isn't in the system or no epochs overlap
+pub fn compare(system: &SolarSystem, table: &HorizonsTable, body: &str, center: &str) -> Option<ComparisonReport> {
+    if center != system.central_body.name {
+        system.find(center)?;
+    }
+    let times = &system.find(body)?.times;
+    let states: Vec<_> = system.relative_states(body, center)?.iter().map(State::vectors).collect();
+    let times = &times[..states.len().min(times.len())];
+
+    let mut samples = Vec::new();
+    for reference in &table.states {
+        let time = reference.julian_date - system.epoch;
+        if let Some((position, velocity)) = interpolate(times, &states, time) {
+            samples.push(ErrorSample {
+                julian_date: reference.julian_date,
+                position_error: Length::km(norm(sub(position, reference.position))),
+                velocity_error: Speed::km_per_s(norm(sub(velocity, reference.velocity))),
+            });
+        }
+    }
+    if samples.is_empty() {
+        return None;
+    }
+    let count = samples.len() as f64;
+    let rms = |error: fn(&ErrorSample) -> f64| (samples.iter().map(|sample| error(sample).powi(2)).sum::<f64>() / count).sqrt();
+    let max = |error: fn(&ErrorSample) -> f64| samples.iter().map(error).fold(0.0, f64::max);
+    let start = samples[0].julian_date;
+    let growth = |error: fn(&ErrorSample) -> f64| {
+        slope(&samples.iter().map(|sample| ((sample.julian_date - start).in_days(), error(sample))).collect::<Vec<_>>())
+    };
+    Some(ComparisonReport {
+        body: body.to_string(),
+        center: center.to_string(),
+        skipped: table.states.len() - samples.len(),
+        max_position_error: Length::km(max(|sample| sample.position_error.in_km())),
+        rms_position_error: Length::km(rms(|sample| sample.position_error.in_km())),
+        max_velocity_error: Speed::km_per_s(max(|sample| sample.velocity_error.in_km_per_s())),
+        rms_velocity_error: Speed::km_per_s(rms(|sample| sample.velocity_error.in_km_per_s())),
+        position_error_growth: Length::km(growth(|sample| sample.position_error.in_km())),
+        velocity_error_growth: Speed::km_per_s(growth(|sample| sample.velocity_error.in_km_per_s())),
+        samples,
+    })
+}
+
+/// One report per table, for every table whose target and center are in the system, see `HorizonsTable::compare`
+pub fn compare_tables(system: &SolarSystem, tables: &[HorizonsTable]) -> Vec<ComparisonReport> {
+    tables.iter().filter_map(|table| table.compare(system)).collect()
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {} over {} epochs: position error max {:.3} km, rms {:.3} km, growth {:.3} km/day; \
+             velocity error max {:.6} km/s, rms {:.6} km/s, growth {:.3e} km/s/day",
+            self.body,
+            self.center,
+            self.samples.len(),
+            self.max_position_error.in_km(),
+            self.rms_position_error.in_km(),
+            self.position_error_growth.in_km(),
+            self.max_velocity_error.in_km_per_s(),
+            self.rms_velocity_error.in_km_per_s(),
+            self.velocity_error_growth.in_km_per_s()
+        )?;
+        if self.skipped > 0 {
+            write!(f, " ({} epochs outside the propagated span skipped)", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orbit_propagration::propagate_system_two_body;
+    use crate::planet::DEFAULT_DATA_PATH;
+
+    /// Earth from the Sun at J2000 in the default layout, AU-D and ICRF
+    const DEFAULT_LAYOUT: &str = "*******************************************************************************
+Target body name: Earth (399)                     {source: DE441}
+Center body name: Sun (10)                        {source: DE441}
+*******************************************************************************
+Output units    : AU-D
+Reference frame : ICRF
+*******************************************************************************
+$$SOE
+2451545.000000000 = A.D. 2000-Jan-01 12:00:00.0000 TDB
+ X =-1.771350992727098E-01 Y = 8.874285221198165E-01 Z = 3.847428064814481E-01
+ VX=-1.720762506872895E-02 VY=-2.898167023316531E-03 VZ=-1.256395336186371E-03
+$$EOE
+";
+
+    #[test]
+    fn default_layout_is_converted_to_km_and_the_ecliptic() {
+        let table: HorizonsTable = DEFAULT_LAYOUT.parse().expect("table should parse");
+        assert_eq!((table.target.as_deref(), table.center.as_str()), (Some("Earth"), "Sun"));
+        let [state] = table.states.as_slice() else { panic!("expected one state, got {}", table.states.len()) };
+        assert_eq!(state.julian_date, JulianDate::days(2451545.0));
+        let ecliptic = Frame::ecliptic("Sun");
+        let position = state.position_in(&ecliptic).expect("rows are in the center's ecliptic frame").in_km();
+        let expected = equatorial_to_ecliptic(scale([-1.771350992727098E-01, 8.874285221198165E-01, 3.847428064814481E-01], KM_PER_AU));
+        assert!(norm(sub(position, expected)) < 1E-6, "{:?} vs {:?}", position, expected);
+        assert!(position[2].abs() < 1E4, "Earth should sit near the ecliptic, z = {} km", position[2]);
+        let speed = state.velocity_in(&ecliptic).expect("same frame").magnitude().in_km_per_s();
+        assert!((speed - 30.3).abs() < 0.1, "Earth's speed {} km/s", speed);
+        assert!(state.position_in(&Frame::ecliptic("Earth")).is_none());
+    }
+
+    #[test]
+    fn unknown_output_units_are_refused() {
+        let error = DEFAULT_LAYOUT.replace("AU-D", "KM-H").parse::<HorizonsTable>().expect_err("KM-H isn't a Horizons unit");
+        let LoadError::InvalidField { field, location, .. } = &error else { panic!("expected an invalid field, got {:?}", error) };
+        assert_eq!((field.as_str(), location.line), ("Output units", Some(5)));
+    }
+
+    #[test]
+    fn comparison_reports_a_known_offset() {
+        let mut system = SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load");
+        propagate_system_two_body(&mut system, Time::days(10.0), Time::days(1.0));
+        // The propagated Earth itself as a KM-S CSV table, pushed 5 km further along x each day, and one row past the run
+        let mut table = String::from("Target body name: Earth (399)\nCenter body name: Sun (10)\nOutput units    : KM-S\n$$SOE\n");
+        for day in (0..=10).chain([20]) {
+            let state = system.state("Earth", day.min(10)).expect("Earth has a history");
+            let [x, y, z] = add(state.position, [5.0 * day as f64, 0.0, 0.0]);
+            let [vx, vy, vz] = state.velocity;
+            let julian_date = system.epoch.in_days() + day as f64;
+            table.push_str(&format!("{}, A.D., {}, {}, {}, {}, {}, {},\n", julian_date, x, y, z, vx, vy, vz));
+        }
+        table.push_str("$$EOE\n");
+        let report = table.parse::<HorizonsTable>().expect("table should parse").compare(&system).expect("the run overlaps the table");
+
+        assert_eq!((report.samples.len(), report.skipped), (11, 1));
+        let close = |value: f64, expected: f64| (value - expected).abs() < 1E-3;
+        assert!(close(report.max_position_error.in_km(), 50.0), "{}", report);
+        assert!(close(report.rms_position_error.in_km(), 5.0 * 35f64.sqrt()), "{}", report);
+        assert!(close(report.position_error_growth.in_km(), 5.0), "{}", report);
+        assert!(report.max_velocity_error.in_km_per_s() < 1E-9, "{}", report);
+    }
+}
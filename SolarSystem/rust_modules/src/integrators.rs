@@ -0,0 +1,302 @@
+use crate::units::Time;
+
+/// Accelerations (km/s^2) of every body given the time, positions (km) and velocities (km/s)
+pub type Acceleration<'a> = dyn Fn(Time, &[[f64; 3]], &[[f64; 3]]) -> Vec<[f64; 3]> + 'a;
+
+/// A numerical integrator for the second order equations of motion of a set of bodies
+pub trait Integrator {
+    /// Advances positions and velocities from `time` by at most `max_step`,
+    /// returning how far it actually advanced. Fixed step integrators only ever take
+    /// less than their step to land exactly on `max_step`.
+    fn step(
+        &mut self,
+        time: Time,
+        max_step: Time,
+        positions: &mut [[f64; 3]],
+        velocities: &mut [[f64; 3]],
+        acceleration: &Acceleration,
+    ) -> Time;
+}
+
+/// Positions and velocities packed one after the other, [x0, y0, z0, x1, ... vx0, vy0, ...]
+fn pack(positions: &[[f64; 3]], velocities: &[[f64; 3]]) -> Vec<f64> {
+    positions.iter().chain(velocities.iter()).flatten().copied().collect()
+}
+
+/// Reverse of `pack`
+fn unpack(state: &[f64], positions: &mut [[f64; 3]], velocities: &mut [[f64; 3]]) {
+    let n = positions.len();
+    for (i, chunk) in state.chunks_exact(3).enumerate() {
+        let vector = [chunk[0], chunk[1], chunk[2]];
+        if i < n {
+            positions[i] = vector;
+        }
+        else {
+            velocities[i - n] = vector;
+        }
+    }
+}
+
+/// Time derivative of a packed state, the velocities followed by the accelerations
+fn derivative(time: Time, state: &[f64], acceleration: &Acceleration) -> Vec<f64> {
+    let n = state.len() / 6;
+    let mut positions = vec![[0.0; 3]; n];
+    let mut velocities = vec![[0.0; 3]; n];
+    unpack(state, &mut positions, &mut velocities);
+    let accelerations = acceleration(time, &positions, &velocities);
+    pack(&velocities, &accelerations)
+}
+
+/// state + sum(weight * stage) * step, for building up Runge-Kutta stages
+fn combine(state: &[f64], stages: &[&Vec<f64>], weights: &[f64], step: Time) -> Vec<f64> {
+    let step = step.in_seconds();
+    let mut result = state.to_vec();
+    for (stage, weight) in stages.iter().zip(weights) {
+        if *weight == 0.0 {
+            continue;
+        }
+        for (value, rate) in result.iter_mut().zip(stage.iter()) {
+            *value += weight * step * rate;
+        }
+    }
+    result
+}
+
+/// Classic fixed step fourth order Runge-Kutta
+pub struct RungeKutta4 {
+    pub step_size: Time,
+}
+
+impl RungeKutta4 {
+    pub fn new(step_size: Time) -> Self {
+        assert!(step_size > Time::default(), "Step size must be positive, got {}", step_size);
+        Self { step_size }
+    }
+}
+
+impl Integrator for RungeKutta4 {
+    fn step(
+        &mut self,
+        time: Time,
+        max_step: Time,
+        positions: &mut [[f64; 3]],
+        velocities: &mut [[f64; 3]],
+        acceleration: &Acceleration,
+    ) -> Time {
+        let h = self.step_size.min(max_step);
+        let state = pack(positions, velocities);
+        let k1 = derivative(time, &state, acceleration);
+        let k2 = derivative(time + 0.5 * h, &combine(&state, &[&k1], &[0.5], h), acceleration);
+        let k3 = derivative(time + 0.5 * h, &combine(&state, &[&k2], &[0.5], h), acceleration);
+        let k4 = derivative(time + h, &combine(&state, &[&k3], &[1.0], h), acceleration);
+        let next = combine(&state, &[&k1, &k2, &k3, &k4], &[1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0], h);
+        unpack(&next, positions, velocities);
+        h
+    }
+}
+
+/// Dormand-Prince 5(4) coefficients, the fifth order solution is the one kept
+const DP_C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const DP_A: [[f64; 6]; 7] = [
+    [0.0; 6],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+    [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+    [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+const DP_B5: [f64; 7] = [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0, 0.0];
+const DP_B4: [f64; 7] = [
+    5179.0 / 57600.0, 0.0, 7571.0 / 16695.0, 393.0 / 640.0, -92097.0 / 339200.0, 187.0 / 2100.0, 1.0 / 40.0,
+];
+
+/// Adaptive embedded Runge-Kutta (Dormand-Prince 5(4)).
+/// Each step is accepted only if the estimated local error is within
+/// absolute_tolerance + relative_tolerance * |state| for every component, and the step size
+/// is adjusted after every attempt to stay close to that limit.
+/// This is the only adaptive integrator here, there is no eighth order DOP853, so long runs needing more accuracy
+/// tighten the tolerances instead.
+pub struct DormandPrince45 {
+    pub relative_tolerance: f64,
+    pub absolute_tolerance: f64,
+    pub step_size: Time, // the size the next step will try
+    pub min_step: Time,
+    pub max_step: Time,
+}
+
+impl DormandPrince45 {
+    pub fn new(relative_tolerance: f64, absolute_tolerance: f64) -> Self {
+        assert!(relative_tolerance > 0.0 && absolute_tolerance > 0.0, "Tolerances must be positive");
+        Self {
+            relative_tolerance,
+            absolute_tolerance,
+            step_size: Time::seconds(60.0),
+            min_step: Time::seconds(1E-6),
+            max_step: Time::seconds(f64::INFINITY),
+        }
+    }
+}
+
+impl Integrator for DormandPrince45 {
+    fn step(
+        &mut self,
+        time: Time,
+        max_step: Time,
+        positions: &mut [[f64; 3]],
+        velocities: &mut [[f64; 3]],
+        acceleration: &Acceleration,
+    ) -> Time {
+        let state = pack(positions, velocities);
+        loop {
+            let h = self.step_size.min(self.max_step).min(max_step);
+            let mut stages: Vec<Vec<f64>> = Vec::with_capacity(7);
+            for i in 0..7 {
+                let stage_refs: Vec<&Vec<f64>> = stages.iter().collect();
+                let stage_state = combine(&state, &stage_refs, &DP_A[i][..i], h);
+                stages.push(derivative(time + DP_C[i] * h, &stage_state, acceleration));
+            }
+            let stage_refs: Vec<&Vec<f64>> = stages.iter().collect();
+            let next = combine(&state, &stage_refs, &DP_B5, h);
+
+            // Root mean square of the error estimate scaled by the tolerance
+            let mut error = 0.0;
+            for (i, value) in next.iter().enumerate() {
+                let difference: f64 = (0..7).map(|s| (DP_B5[s] - DP_B4[s]) * stages[s][i]).sum::<f64>() * h.in_seconds();
+                let scale = self.absolute_tolerance + self.relative_tolerance * value.abs().max(state[i].abs());
+                error += (difference / scale).powi(2);
+            }
+            let error = (error / next.len() as f64).sqrt();
+
+            // Standard step size controller, limited so the step never changes too sharply
+            let factor = if error == 0.0 { 5.0 } else { (0.9 * error.powf(-0.2)).clamp(0.2, 5.0) };
+            if error <= 1.0 || h <= self.min_step {
+                // Don't let a short step used to land on max_step shrink the next one
+                if h >= self.step_size.min(self.max_step) || factor < 1.0 {
+                    self.step_size = (h * factor).max(self.min_step);
+                }
+                unpack(&next, positions, velocities);
+                return h;
+            }
+            self.step_size = (h * factor).max(self.min_step);
+        }
+    }
+}
+
+/// Velocity Verlet, positions move with the starting acceleration then velocities with the average of both ends' accelerations.
+/// Symplectic and second order
+pub struct VelocityVerlet {
+    pub step_size: Time,
+}
+
+impl VelocityVerlet {
+    pub fn new(step_size: Time) -> Self {
+        assert!(step_size > Time::default(), "Step size must be positive, got {}", step_size);
+        Self { step_size }
+    }
+}
+
+impl Integrator for VelocityVerlet {
+    fn step(
+        &mut self,
+        time: Time,
+        max_step: Time,
+        positions: &mut [[f64; 3]],
+        velocities: &mut [[f64; 3]],
+        acceleration: &Acceleration,
+    ) -> Time {
+        let step = self.step_size.min(max_step);
+        let h = step.in_seconds();
+        let start = acceleration(time, positions, velocities);
+        for ((position, velocity), accel) in positions.iter_mut().zip(velocities.iter()).zip(&start) {
+            for k in 0..3 {
+                position[k] += velocity[k] * h + 0.5 * accel[k] * h * h;
+            }
+        }
+        // Euler prediction of the end velocities over the whole step, for velocity dependent forces
+        let mut predicted = velocities.to_vec();
+        for (velocity, accel) in predicted.iter_mut().zip(&start) {
+            for k in 0..3 {
+                velocity[k] += accel[k] * h;
+            }
+        }
+        let end = acceleration(time + step, positions, &predicted);
+        for ((velocity, a0), a1) in velocities.iter_mut().zip(&start).zip(&end) {
+            for k in 0..3 {
+                velocity[k] += 0.5 * (a0[k] + a1[k]) * h;
+            }
+        }
+        step
+    }
+}
+
+/// Drift-kick-drift leapfrog, symplectic and second order with one force evaluation per step
+pub struct Leapfrog {
+    pub step_size: Time,
+}
+
+impl Leapfrog {
+    pub fn new(step_size: Time) -> Self {
+        assert!(step_size > Time::default(), "Step size must be positive, got {}", step_size);
+        Self { step_size }
+    }
+}
+
+impl Integrator for Leapfrog {
+    fn step(
+        &mut self,
+        time: Time,
+        max_step: Time,
+        positions: &mut [[f64; 3]],
+        velocities: &mut [[f64; 3]],
+        acceleration: &Acceleration,
+    ) -> Time {
+        let step = self.step_size.min(max_step);
+        let h = step.in_seconds();
+        for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+            for k in 0..3 {
+                position[k] += 0.5 * velocity[k] * h;
+            }
+        }
+        let kick = acceleration(time + 0.5 * step, positions, velocities);
+        for ((position, velocity), accel) in positions.iter_mut().zip(velocities.iter_mut()).zip(&kick) {
+            for k in 0..3 {
+                velocity[k] += accel[k] * h;
+                position[k] += 0.5 * velocity[k] * h;
+            }
+        }
+        step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::{dot, norm, scale};
+
+    const MU: f64 = 1.32712440018E11; // km^3/s^2, the Sun
+
+    /// Specific orbital energy of a body around a fixed point mass, km^2/s^2
+    fn energy(position: [f64; 3], velocity: [f64; 3]) -> f64 {
+        0.5 * dot(velocity, velocity) - MU / norm(position)
+    }
+
+    #[test]
+    fn rk4_keeps_two_body_energy() {
+        let mut positions = [[1.495978707E8, 0.0, 0.0]];
+        // Faster than circular, so the orbit is eccentric and the step matters
+        let mut velocities = [[0.0, 1.1 * (MU / positions[0][0]).sqrt(), 0.0]];
+        let gravity = |_time: Time, positions: &[[f64; 3]], _velocities: &[[f64; 3]]| -> Vec<[f64; 3]> {
+            positions.iter().map(|&position| scale(position, -MU / norm(position).powi(3))).collect()
+        };
+        let start = energy(positions[0], velocities[0]);
+        let mut integrator = RungeKutta4::new(Time::days(1.0));
+        let span = Time::days(365.25);
+        let mut time = Time::default();
+        while time < span {
+            time += integrator.step(time, span - time, &mut positions, &mut velocities, &gravity);
+        }
+        let drift = ((energy(positions[0], velocities[0]) - start) / start).abs();
+        assert!(drift < 1e-8, "relative energy drift {} over a year", drift);
+    }
+}
@@ -0,0 +1,338 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::epoch::J2000;
+use crate::error::{LoadError, Location};
+use crate::schema::{BodyRecord, RatesRecord, SystemRecord};
+use crate::units::Length;
+
+/// The phys_par table gives masses in units of 10^24 kg
+const PHYS_PAR_MASS_UNIT: f64 = 1E24;
+/// Table 1 of approx_pos is for J2000 and valid from 1800 AD to 2050 AD
+const APPROX_POS_VALID_FROM_JD: f64 = 2378496.5;
+const APPROX_POS_VALID_TO_JD: f64 = 2470172.5;
+
+/// Elements and rates of one body from the approx_pos table, in the table's own units (au and degrees)
+#[derive(Debug, Clone, PartialEq)]
+struct ApproxPosRow {
+    name: String,
+    elements: [f64; 6],
+    rates: [f64; 6],
+}
+
+/// Replaces the handful of HTML entities the JPL pages use
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&plusmn;", "±")
+        .replace("&#177;", "±")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Drops everything between < and >
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    decode_entities(&text)
+}
+
+/// Text inside every <pre> block of a page, or the whole input if it has none (already just the text)
+fn pre_blocks(page: &str) -> Vec<String> {
+    let lower = page.to_lowercase();
+    let mut blocks = Vec::new();
+    let mut search_from = 0;
+    while let Some(start) = lower[search_from..].find("<pre").map(|i| i + search_from) {
+        let Some(content_start) = lower[start..].find('>').map(|i| i + start + 1) else { break };
+        let end = lower[content_start..].find("</pre>").map_or(page.len(), |i| i + content_start);
+        blocks.push(strip_tags(&page[content_start..end]));
+        search_from = end;
+    }
+    if blocks.is_empty() {
+        blocks.push(page.to_string());
+    }
+    blocks
+}
+
+/// The number a piece of text starts with, ignoring anything after it such as ±0.1 or a [footnote]
+fn leading_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let mut end = 0;
+    for (i, c) in text.char_indices() {
+        let previous = text[..i].chars().last();
+        let allowed = c.is_ascii_digit()
+            || c == '.'
+            || ((c == '-' || c == '+') && (i == 0 || matches!(previous, Some('e' | 'E'))))
+            || ((c == 'e' || c == 'E') && i > 0);
+        if !allowed {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+    // Back off a trailing exponent marker or sign that had nothing after it
+    let mut candidate = &text[..end];
+    while !candidate.is_empty() && candidate.parse::<f64>().is_err() {
+        candidate = &candidate[..candidate.len() - 1];
+    }
+    candidate.parse().ok()
+}
+
+/// Rows of approx_pos that aren't planets, the data file only holds the eight planets
+const SKIPPED_BODIES: [&str; 1] = ["Pluto"];
+
+/// Names used in the JPL tables that the data file spells differently
+fn body_name(name: &str) -> String {
+    match name {
+        "EM Bary" => "Earth".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Rows of the first element table in the approx_pos page: a line with a name and six elements,
+/// then a line with the six rates per century. Reading stops at the first name seen twice,
+/// which is where the second (3000 BC to 3000 AD) table starts
+fn parse_approx_pos(page: &str, path: Option<&Path>) -> Result<Vec<ApproxPosRow>, LoadError> {
+    let mut rows: Vec<ApproxPosRow> = Vec::new();
+    'blocks: for block in pre_blocks(page) {
+        let mut pending: Option<(String, [f64; 6])> = None;
+        for line in block.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let numbers: Vec<f64> = tokens.iter().filter_map(|token| token.parse::<f64>().ok()).collect();
+            let name_tokens: Vec<&str> = tokens.iter().copied().filter(|token| token.parse::<f64>().is_err()).collect();
+            if numbers.len() != 6 || name_tokens.len() + 6 != tokens.len() {
+                pending = None;
+                continue;
+            }
+            if name_tokens.is_empty() {
+                // A rate line, belonging to the element line right above it
+                if let Some((name, elements)) = pending.take() {
+                    rows.push(ApproxPosRow { name, elements, rates: [numbers[0], numbers[1], numbers[2], numbers[3], numbers[4], numbers[5]] });
+                }
+            }
+            else {
+                let name = body_name(&name_tokens.join(" "));
+                if rows.iter().any(|row| row.name == name) {
+                    break 'blocks;
+                }
+                pending = Some((name, [numbers[0], numbers[1], numbers[2], numbers[3], numbers[4], numbers[5]]));
+            }
+        }
+        if !rows.is_empty() {
+            break;
+        }
+    }
+    if rows.is_empty() {
+        return Err(LoadError::Syntax {
+            location: Location { path: path.map(Path::to_path_buf), line: None, column: None },
+            message: "no table of elements and rates found".to_string(),
+        });
+    }
+    Ok(rows)
+}
+
+/// Cells of every table row in an HTML page, or whitespace separated words of every line for plain text
+fn table_rows(page: &str) -> Vec<Vec<String>> {
+    let lower = page.to_lowercase();
+    if !lower.contains("<tr") {
+        return page.lines().map(|line| line.split_whitespace().map(str::to_string).collect()).collect();
+    }
+    let mut rows = Vec::new();
+    for (row_start, _) in lower.match_indices("<tr") {
+        let row_end = lower[row_start + 3..].find("<tr").map_or(page.len(), |i| i + row_start + 3);
+        let row_lower = &lower[row_start..row_end];
+        let row = &page[row_start..row_end];
+        let mut cells = Vec::new();
+        let mut search_from = 0;
+        while let Some(cell_start) = [row_lower[search_from..].find("<td"), row_lower[search_from..].find("<th")]
+            .into_iter()
+            .flatten()
+            .min()
+            .map(|i| i + search_from)
+        {
+            let Some(content_start) = row_lower[cell_start..].find('>').map(|i| i + cell_start + 1) else { break };
+            let content_end = ["</td", "</th", "<td", "<th"]
+                .iter()
+                .filter_map(|tag| row_lower[content_start..].find(tag))
+                .min()
+                .map_or(row.len(), |i| i + content_start);
+            let text = strip_tags(&row[content_start..content_end]);
+            cells.push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+            search_from = content_end;
+        }
+        rows.push(cells);
+    }
+    rows
+}
+
+/// Mean radius (km) and mass (kg) of every body in the phys_par table, by name.
+/// In HTML the columns are found from the header row, plain text is taken to be in the page's
+/// column order (equatorial radius, mean radius, mass, ...)
+fn parse_phys_par(page: &str, path: Option<&Path>) -> Result<BTreeMap<String, (f64, f64)>, LoadError> {
+    let rows = table_rows(page);
+    let header = rows.iter().find(|row| row.iter().any(|cell| cell.to_lowercase().contains("mean radius")));
+    let column = |label: &str, default: usize| {
+        header
+            .and_then(|row| row.iter().position(|cell| cell.to_lowercase().contains(label)))
+            .unwrap_or(default)
+    };
+    let (radius_column, mass_column) = if header.is_some() { (column("mean radius", 2), column("mass", 3)) } else { (2, 3) };
+
+    let mut bodies = BTreeMap::new();
+    for row in &rows {
+        let Some(name) = row.first().filter(|name| leading_number(name).is_none() && !name.is_empty()) else {
+            continue;
+        };
+        let radius = row.get(radius_column).and_then(|cell| leading_number(cell));
+        let mass = row.get(mass_column).and_then(|cell| leading_number(cell));
+        if let (Some(radius), Some(mass)) = (radius, mass) {
+            bodies.insert(body_name(name), (radius, mass * PHYS_PAR_MASS_UNIT));
+        }
+    }
+    if bodies.is_empty() {
+        return Err(LoadError::Syntax {
+            location: Location { path: path.map(Path::to_path_buf), line: None, column: None },
+            message: "no table of mean radii and masses found".to_string(),
+        });
+    }
+    Ok(bodies)
+}
+
+/// Builds the data file's schema from saved copies of JPL's approx_pos and phys_par pages,
+/// either the full HTML or just the text of the tables. Bodies that are in the element table
+/// but not the physical one are reported as missing their radius
+fn import_record(approx_pos: &str, phys_par: &str, approx_pos_path: Option<&Path>, phys_par_path: Option<&Path>) -> Result<SystemRecord, LoadError> {
+    let rows = parse_approx_pos(approx_pos, approx_pos_path)?;
+    let physical = parse_phys_par(phys_par, phys_par_path)?;
+    let mut bodies = BTreeMap::new();
+    for row in rows.into_iter().filter(|row| !SKIPPED_BODIES.contains(&row.name.as_str())) {
+        let (radius, mass) = *physical.get(&row.name).ok_or_else(|| LoadError::MissingField {
+            body: Some(row.name.clone()),
+            field: "meanradius_km".to_string(),
+            location: Location { path: phys_par_path.map(Path::to_path_buf), line: None, column: None },
+        })?;
+        let [a, e, i, l, peri, node] = row.elements;
+        let [a_rate, e_rate, i_rate, l_rate, peri_rate, node_rate] = row.rates;
+        let record = BodyRecord {
+            // Rounded to 0.1 km, the same as the Python scraper did
+            semi_major_axis_km: (Length::au(a).in_km() * 10.0).round() / 10.0,
+            eccentricity: e,
+            inclination_degrees: i,
+            mean_longitude_degrees: l,
+            longitude_of_perihelion_degrees: peri,
+            longitude_of_the_ascending_node_degrees: node,
+            meanradius_km: radius,
+            mass_kg: mass,
+            rates_per_century: Some(RatesRecord {
+                semi_major_axis_km: (Length::au(a_rate).in_km() * 10.0).round() / 10.0,
+                eccentricity: e_rate,
+                inclination_degrees: i_rate,
+                mean_longitude_degrees: l_rate,
+                longitude_of_perihelion_degrees: peri_rate,
+                longitude_of_the_ascending_node_degrees: node_rate,
+            }),
+            zonal_harmonics: None,
+            atmosphere: None,
+            spacecraft: None,
+            rotation: None,
+            moons: BTreeMap::new(),
+        };
+        bodies.insert(row.name, record);
+    }
+    let record = SystemRecord {
+        number_of_bodies: bodies.len(),
+        epoch_jd: J2000.in_days(),
+        valid_from_jd: Some(APPROX_POS_VALID_FROM_JD),
+        valid_to_jd: Some(APPROX_POS_VALID_TO_JD),
+        central_body: None,
+        bodies,
+    };
+    record.validate().map_err(|violation| LoadError::InvalidField {
+        body: violation.body,
+        field: violation.field,
+        reason: violation.reason,
+        location: Location { path: approx_pos_path.map(Path::to_path_buf), line: None, column: None },
+    })?;
+    Ok(record)
+}
+
+/// The contents of celestial_bodies_data.toml built from the text of the two JPL pages, see `import_record`
+pub fn import_jpl(approx_pos: &str, phys_par: &str) -> Result<String, LoadError> {
+    let record = import_record(approx_pos, phys_par, None, None)?;
+    Ok(toml::to_string(&record).expect("Schema records always serialize"))
+}
+
+/// Same as `import_jpl` for pages saved to disk, writing the data file to `output`
+pub fn import_jpl_files(approx_pos: impl AsRef<Path>, phys_par: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<(), LoadError> {
+    let read = |path: &Path| fs::read_to_string(path).map_err(|source| LoadError::Io { path: path.to_path_buf(), source });
+    let (approx_pos, phys_par, output) = (approx_pos.as_ref(), phys_par.as_ref(), output.as_ref());
+    let record = import_record(&read(approx_pos)?, &read(phys_par)?, Some(approx_pos), Some(phys_par))?;
+    let contents = toml::to_string(&record).expect("Schema records always serialize");
+    fs::write(output, contents).map_err(|source| LoadError::Io { path: output.to_path_buf(), source })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The start of Table 1 of approx_pos and the first row of Table 2a, which has to be left alone
+    const APPROX_POS: &str = "<html><body><h2>Table 1</h2><pre>
+               a              e               I                L            long.peri.      long.node.
+           au, au/Cy     rad, rad/Cy     deg, deg/Cy      deg, deg/Cy      deg, deg/Cy     deg, deg/Cy
+-----------------------------------------------------------------------------------------------------------
+Mercury   0.38709927      0.20563593      7.00497902      252.25032350     77.45779628     48.33076593
+          0.00000037      0.00001906     -0.00594749   149472.67411175      0.15940013     -0.12534081
+EM&nbsp;Bary   1.00000261      0.01671123     -0.00001531      100.46457166    102.93768193      0.0
+          0.00000562     -0.00004392     -0.01294668    35999.37244981      0.32327364      0.0
+</pre><h2>Table 2a</h2><pre>
+Mercury   0.38709843      0.20563661      7.00559432      252.25166724     77.45771895     48.33961819
+          0.00000000      0.00002123     -0.00590158   149472.67486623      0.15940013     -0.12214182
+</pre></body></html>";
+
+    const PHYS_PAR: &str = "<table>
+<tr><th>Planet</th><th>Equatorial<br>Radius (km)</th><th>Mean<br>Radius (km)</th><th>Mass<br>(x 10<sup>24</sup> kg)</th></tr>
+<tr><td>Mercury</td><td>2440.53&plusmn;0.04</td><td>2439.4&plusmn;0.1</td><td>0.330103</td></tr>
+<tr><td>Earth</td><td>6378.1366&plusmn;0.0001</td><td>6371.0084&plusmn;0.0001</td><td>5.97217</td></tr>
+</table>";
+
+    #[test]
+    fn first_element_table_and_physical_parameters_are_read() {
+        let record: SystemRecord = toml::from_str(&import_jpl(APPROX_POS, PHYS_PAR).expect("pages should import"))
+            .expect("the import should be in the current schema");
+        assert_eq!(record.number_of_bodies, 2);
+        assert_eq!((record.valid_from_jd, record.valid_to_jd), (Some(APPROX_POS_VALID_FROM_JD), Some(APPROX_POS_VALID_TO_JD)));
+
+        let mercury = &record.bodies["Mercury"];
+        assert_eq!(mercury.semi_major_axis_km, 57909226.5);
+        assert_eq!(mercury.inclination_degrees, 7.00497902, "taken from Table 1 rather than Table 2a");
+        assert_eq!((mercury.meanradius_km, mercury.mass_kg), (2439.4, 0.330103 * PHYS_PAR_MASS_UNIT));
+        let rates = mercury.rates_per_century.as_ref().expect("Table 1 has rates");
+        assert_eq!((rates.semi_major_axis_km, rates.mean_longitude_degrees), (55.4, 149472.67411175));
+
+        let earth = record.bodies.get("Earth").expect("the Earth-Moon barycenter row becomes Earth");
+        assert_eq!((earth.meanradius_km, earth.mass_kg), (6371.0084, 5.97217 * PHYS_PAR_MASS_UNIT));
+    }
+
+    #[test]
+    fn bodies_without_physical_parameters_are_reported() {
+        let phys_par = PHYS_PAR.replace("<tr><td>Earth</td>", "<tr><td>Venus</td>");
+        let error = import_jpl(APPROX_POS, &phys_par).expect_err("Earth has no radius or mass");
+        assert!(matches!(&error, LoadError::MissingField { body: Some(body), .. } if body == "Earth"), "{:?}", error);
+        assert!(matches!(import_jpl("no tables here", PHYS_PAR), Err(LoadError::Syntax { .. })));
+    }
+
+    #[test]
+    fn leading_numbers_ignore_uncertainties_and_footnotes() {
+        assert_eq!(leading_number("2439.4±0.1"), Some(2439.4));
+        assert_eq!(leading_number("1.3E-5[3]"), Some(1.3E-5));
+        assert_eq!(leading_number("-12e"), Some(-12.0));
+        assert_eq!(leading_number("Mercury"), None);
+    }
+}
@@ -0,0 +1,207 @@
+use std::f64::consts::{PI, TAU};
+
+/// Convergence tolerance on Kepler's equation, in radians
+const TOLERANCE: f64 = 1e-14;
+/// Upper bound on solver iterations, the bracketing keeps this from ever being reached in practice
+const MAX_ITERATIONS: usize = 100;
+/// Eccentricities within this distance of 1 are treated as parabolic
+const PARABOLIC_TOLERANCE: f64 = 1e-12;
+
+/// Takes an angle in radians and wraps it between 0 and TAU (2*pi)
+pub fn wrap_angle(angle: f64) -> f64 {
+    let wrapped_angle = angle % TAU;
+    if wrapped_angle < 0.0 {
+        wrapped_angle + TAU
+    }
+    else {
+        wrapped_angle
+    }
+}
+
+/// Takes an angle in radians and wraps it between -pi and pi
+fn wrap_angle_signed(angle: f64) -> f64 {
+    let wrapped_angle = wrap_angle(angle);
+    if wrapped_angle > PI {
+        wrapped_angle - TAU
+    }
+    else {
+        wrapped_angle
+    }
+}
+
+/// True if an orbit with this eccentricity should be handled as a parabola
+pub fn is_parabolic(eccentricity: f64) -> bool {
+    (eccentricity - 1.0).abs() < PARABOLIC_TOLERANCE
+}
+
+/// Newton's method kept inside the bracket [lower, upper].
+/// `f` returns the residual and its derivative, which must be increasing over the bracket.
+/// Any step that would leave the bracket is replaced with a bisection, so this always converges.
+fn safeguarded_newton(f: impl Fn(f64) -> (f64, f64), mut lower: f64, mut upper: f64, guess: f64) -> f64 {
+    let mut x = guess.clamp(lower, upper);
+    for _ in 0..MAX_ITERATIONS {
+        let (residual, slope) = f(x);
+        if residual.abs() < TOLERANCE {
+            break;
+        }
+        // Shrink the bracket around the root
+        if residual > 0.0 {
+            upper = x;
+        }
+        else {
+            lower = x;
+        }
+        let newton = x - residual / slope;
+        x = if slope > 0.0 && newton > lower && newton < upper {
+            newton
+        }
+        else {
+            0.5 * (lower + upper)
+        };
+        if upper - lower < TOLERANCE {
+            break;
+        }
+    }
+    x
+}
+
+/// Solves Kepler's equation for the eccentric anomaly, in radians.
+/// Elliptic orbits:   M = E - e sin(E)
+/// Hyperbolic orbits: M = e sinh(H) - H, the hyperbolic anomaly H is returned
+/// Parabolic orbits:  M = D + D^3 / 3 (Barker's equation), D = tan(true anomaly / 2) is returned
+/// Elliptic results are wrapped between 0 and 2*pi
+pub fn eccentric_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    if is_parabolic(eccentricity) {
+        // Barker's equation has a closed form solution
+        return 2.0 * ((1.5 * mean_anomaly).asinh() / 3.0).sinh();
+    }
+    if eccentricity < 1.0 {
+        // Solve on [0, pi] and use the symmetry of the equation for the other half
+        let mean = wrap_angle_signed(mean_anomaly);
+        let m = mean.abs();
+        // The root is always between M and M + e, and never past pi
+        let guess = if eccentricity < 0.8 { m + eccentricity * m.sin() } else { PI };
+        let anomaly = safeguarded_newton(
+            |e_anom| (e_anom - eccentricity * e_anom.sin() - m, 1.0 - eccentricity * e_anom.cos()),
+            m,
+            (m + eccentricity).min(PI),
+            guess,
+        );
+        wrap_angle(anomaly.copysign(mean))
+    }
+    else {
+        // Odd function, so solve for positive M only
+        let m = mean_anomaly.abs();
+        // e sinh(H) - H lies between (e - 1) sinh(H) and e sinh(H), which brackets H
+        let lower = (m / eccentricity).asinh();
+        let upper = (m / (eccentricity - 1.0)).asinh();
+        let anomaly = safeguarded_newton(
+            |h_anom| (eccentricity * h_anom.sinh() - h_anom - m, eccentricity * h_anom.cosh() - 1.0),
+            lower,
+            upper,
+            lower,
+        );
+        anomaly.copysign(mean_anomaly)
+    }
+}
+
+/// Converts the eccentric (or hyperbolic, or parabolic) anomaly from `eccentric_anomaly` into the true anomaly, in radians.
+/// Elliptic results are wrapped between 0 and 2*pi, otherwise they are between -pi and pi
+pub fn true_anomaly_from_eccentric(eccentric_anomaly: f64, eccentricity: f64) -> f64 {
+    if is_parabolic(eccentricity) {
+        2.0 * eccentric_anomaly.atan()
+    }
+    else if eccentricity < 1.0 {
+        let half = 0.5 * eccentric_anomaly;
+        wrap_angle(2.0 * ((1.0 + eccentricity).sqrt() * half.sin()).atan2((1.0 - eccentricity).sqrt() * half.cos()))
+    }
+    else {
+        2.0 * (((eccentricity + 1.0) / (eccentricity - 1.0)).sqrt() * (0.5 * eccentric_anomaly).tanh()).atan()
+    }
+}
+
+/// Mean anomaly straight to true anomaly, in radians
+pub fn true_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    true_anomaly_from_eccentric(eccentric_anomaly(mean_anomaly, eccentricity), eccentricity)
+}
+
+/// Inverse of `true_anomaly_from_eccentric`, gives the eccentric, hyperbolic or parabolic anomaly for a true anomaly
+pub fn eccentric_from_true(true_anomaly: f64, eccentricity: f64) -> f64 {
+    let half = 0.5 * true_anomaly;
+    if is_parabolic(eccentricity) {
+        half.tan()
+    }
+    else if eccentricity < 1.0 {
+        wrap_angle(2.0 * ((1.0 - eccentricity).sqrt() * half.sin()).atan2((1.0 + eccentricity).sqrt() * half.cos()))
+    }
+    else {
+        2.0 * (((eccentricity - 1.0) / (eccentricity + 1.0)).sqrt() * half.tan()).atanh()
+    }
+}
+
+/// Kepler's equation evaluated forwards, the inverse of `eccentric_anomaly`
+pub fn mean_from_eccentric(eccentric_anomaly: f64, eccentricity: f64) -> f64 {
+    if is_parabolic(eccentricity) {
+        eccentric_anomaly + eccentric_anomaly.powi(3) / 3.0
+    }
+    else if eccentricity < 1.0 {
+        wrap_angle(eccentric_anomaly - eccentricity * eccentric_anomaly.sin())
+    }
+    else {
+        eccentricity * eccentric_anomaly.sinh() - eccentric_anomaly
+    }
+}
+
+/// True anomaly straight to mean anomaly, in radians
+pub fn mean_anomaly(true_anomaly: f64, eccentricity: f64) -> f64 {
+    mean_from_eccentric(eccentric_from_true(true_anomaly, eccentricity), eccentricity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ECCENTRICITIES: [f64; 6] = [0.0, 0.5, 0.99, 1.0, 1.5, 10.0];
+    const MEAN_ANOMALIES: [f64; 9] = [-10.0, -3.0, -1.0, -0.1, 0.0, 0.1, 1.0, 3.0, 10.0];
+
+    /// How far the anomaly from `eccentric_anomaly` is from satisfying the equation it solves
+    fn residual(mean_anomaly: f64, eccentricity: f64) -> f64 {
+        let anomaly = eccentric_anomaly(mean_anomaly, eccentricity);
+        if is_parabolic(eccentricity) {
+            anomaly + anomaly.powi(3) / 3.0 - mean_anomaly
+        }
+        else if eccentricity < 1.0 {
+            wrap_angle_signed(anomaly - eccentricity * anomaly.sin() - mean_anomaly)
+        }
+        else {
+            eccentricity * anomaly.sinh() - anomaly - mean_anomaly
+        }
+    }
+
+    #[test]
+    fn kepler_equation_is_solved_for_every_conic() {
+        for eccentricity in ECCENTRICITIES {
+            for mean_anomaly in MEAN_ANOMALIES {
+                let residual = residual(mean_anomaly, eccentricity);
+                assert!(
+                    residual.abs() < 1e-12 * mean_anomaly.abs().max(1.0),
+                    "residual {} for M = {}, e = {}",
+                    residual,
+                    mean_anomaly,
+                    eccentricity
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mean_anomaly_inverts_true_anomaly() {
+        for eccentricity in ECCENTRICITIES {
+            for mean in [-1.0, -0.1, 0.0, 0.1, 1.0] {
+                let back = mean_anomaly(true_anomaly(mean, eccentricity), eccentricity);
+                let difference = if eccentricity < 1.0 { wrap_angle_signed(back - mean) } else { back - mean };
+                assert!(difference.abs() < 1e-10, "M = {} came back as {} for e = {}", mean, back, eccentricity);
+            }
+        }
+    }
+}
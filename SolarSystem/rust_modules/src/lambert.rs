@@ -0,0 +1,287 @@
+//! Lambert's problem solved with Izzo's method ("Revisiting Lambert's problem", 2015), which finds every
+//! zero and multi revolution solution by Householder iterations on a single variable
+
+use std::f64::consts::PI;
+
+use serde::Serialize;
+
+use crate::frames::State;
+use crate::units::GravitationalParameter;
+use crate::vector::{add, cross, norm, scale, sub};
+
+const HOUSEHOLDER_ITERATIONS: usize = 15;
+/// Between these distances of x from 1 the time of flight comes from Lagrange's form and below them from Battin's series
+const LAGRANGE_DISTANCE: f64 = 0.2;
+const BATTIN_DISTANCE: f64 = 0.01;
+
+/// Which of the two solutions with the same number of whole revolutions, the left one has the lower energy
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LambertBranch {
+    Single, // the zero revolution solution
+    Left,
+    Right,
+}
+
+/// One transfer orbit between the two positions in the given time
+#[derive(Debug, Clone, PartialEq)]
+pub struct LambertSolution {
+    pub revolutions: u32,
+    pub branch: LambertBranch,
+    pub departure: State, // at the departure position with the transfer orbit's velocity, in the frame it was asked in
+    pub arrival: State,
+}
+
+/// The geometry of one problem in Izzo's non-dimensional variables
+struct Geometry {
+    lambda: f64,
+    time: f64, // non-dimensional time of flight
+}
+
+impl Geometry {
+    /// Non-dimensional time of flight for x, from Battin's series near x = 1, Lagrange's form a little further out
+    /// and Lancaster's everywhere else
+    fn time_of_flight(&self, x: f64, revolutions: u32) -> f64 {
+        let lambda = self.lambda;
+        let distance = (x - 1.0).abs();
+        let n = revolutions as f64;
+        if distance < LAGRANGE_DISTANCE && distance > BATTIN_DISTANCE {
+            let a = 1.0 / (1.0 - x * x);
+            return if a > 0.0 {
+                let alpha = 2.0 * x.acos();
+                let beta = 2.0 * (lambda * lambda / a).sqrt().asin().copysign(lambda);
+                a * a.sqrt() * ((alpha - alpha.sin()) - (beta - beta.sin()) + 2.0 * PI * n) / 2.0
+            }
+            else {
+                let alpha = 2.0 * x.acosh();
+                let beta = 2.0 * (-lambda * lambda / a).sqrt().asinh().copysign(lambda);
+                -a * (-a).sqrt() * ((beta - beta.sinh()) - (alpha - alpha.sinh())) / 2.0
+            };
+        }
+        let k = lambda * lambda;
+        let e = x * x - 1.0;
+        let rho = e.abs();
+        let z = (1.0 + k * e).sqrt();
+        if distance < BATTIN_DISTANCE {
+            let eta = z - lambda * x;
+            let s1 = 0.5 * (1.0 - lambda - x * eta);
+            let q = 4.0 / 3.0 * hypergeometric(s1, 1e-11);
+            return (eta.powi(3) * q + 4.0 * lambda * eta) / 2.0 + n * PI / rho.powf(1.5);
+        }
+        let y = rho.sqrt();
+        let g = x * z - lambda * e;
+        let d = if e < 0.0 { n * PI + g.acos() } else { (y * (z - lambda * x) + g).ln() };
+        (x - lambda * z - d / y) / e
+    }
+
+    /// First three derivatives of the time of flight with respect to x, at x where the time of flight is `time`
+    fn derivatives(&self, x: f64, time: f64) -> (f64, f64, f64) {
+        let l2 = self.lambda * self.lambda;
+        let l3 = l2 * self.lambda;
+        let one_minus_x2 = 1.0 - x * x;
+        let y = (1.0 - l2 * one_minus_x2).sqrt();
+        let (y2, y3) = (y * y, y * y * y);
+        let first = (3.0 * time * x - 2.0 + 2.0 * l3 * x / y) / one_minus_x2;
+        let second = (3.0 * time + 5.0 * x * first + 2.0 * (1.0 - l2) * l3 / y3) / one_minus_x2;
+        let third = (7.0 * x * second + 8.0 * first - 6.0 * (1.0 - l2) * l2 * l3 * x / y3 / y2) / one_minus_x2;
+        (first, second, third)
+    }
+
+    /// x giving this problem's time of flight with the given revolutions, by Householder iterations from a guess
+    fn householder(&self, mut x: f64, revolutions: u32, tolerance: f64) -> f64 {
+        for _ in 0..HOUSEHOLDER_ITERATIONS {
+            let time = self.time_of_flight(x, revolutions);
+            let (first, second, third) = self.derivatives(x, time);
+            let delta = time - self.time;
+            let first2 = first * first;
+            let next = x - delta * (first2 - delta * second / 2.0)
+                / (first * (first2 - delta * second) + third * delta * delta / 6.0);
+            let error = (x - next).abs();
+            x = next;
+            if error < tolerance {
+                break;
+            }
+        }
+        x
+    }
+
+    /// Most whole revolutions a solution can make in this time of flight
+    fn max_revolutions(&self) -> u32 {
+        let lambda = self.lambda;
+        let mut most = (self.time / PI).floor() as u32;
+        let t00 = lambda.acos() + lambda * (1.0 - lambda * lambda).sqrt();
+        let t0 = t00 + most as f64 * PI;
+        if self.time < t0 && most > 0 {
+            // Find the minimum time of flight for `most` revolutions with Halley iterations
+            let mut x = 0.0;
+            let mut minimum_time = t0;
+            for _ in 0..12 {
+                let (first, second, third) = self.derivatives(x, minimum_time);
+                let next = if first != 0.0 { x - first * second / (second * second - first * third / 2.0) } else { x };
+                let error = (x - next).abs();
+                x = next;
+                if error < 1e-13 {
+                    break;
+                }
+                minimum_time = self.time_of_flight(x, most);
+            }
+            if minimum_time > self.time {
+                most -= 1;
+            }
+        }
+        most
+    }
+}
+
+/// Gauss hypergeometric function 2F1(3, 1, 5/2, z) summed until the terms fall below `tolerance`
+fn hypergeometric(z: f64, tolerance: f64) -> f64 {
+    let mut sum: f64 = 1.0;
+    let mut term: f64 = 1.0;
+    let mut j = 0.0;
+    while term.abs() > tolerance {
+        term *= (3.0 + j) * (1.0 + j) / (2.5 + j) * z / (j + 1.0);
+        sum += term;
+        j += 1.0;
+    }
+    sum
+}
+
+/// Every orbit from the position of `departure` to that of `arrival`, in a frame centred on a body with gravitational
+/// parameter mu, taking the time between them with up to `max_revolutions` whole revolutions on the way.
+/// Only the positions and times of the two states are used.
+/// Prograde transfers move counterclockwise seen from +z, retrograde ones clockwise.
+/// Empty when the states are in different frames, the time of flight isn't positive or the positions are in line with
+/// the centre, which leaves the plane undefined
+pub fn solve_lambert(
+    departure: &State,
+    arrival: &State,
+    mu: GravitationalParameter,
+    prograde: bool,
+    max_revolutions: u32,
+) -> Vec<LambertSolution> {
+    if departure.frame != arrival.frame {
+        return Vec::new();
+    }
+    let (time_of_flight, mu) = ((arrival.time - departure.time).in_seconds(), mu.in_km3_per_s2());
+    let (start, end) = (departure.position, arrival.position);
+    let chord = norm(sub(end, start));
+    let (r1, r2) = (norm(start), norm(end));
+    let normal = cross(start, end);
+    if time_of_flight <= 0.0 || norm(normal) <= 1e-12 * r1 * r2 {
+        return Vec::new();
+    }
+    let semiperimeter = (r1 + r2 + chord) / 2.0;
+    let (unit_1, unit_2) = (scale(start, 1.0 / r1), scale(end, 1.0 / r2));
+    let unit_normal = scale(normal, 1.0 / norm(normal));
+    let mut lambda = (1.0 - chord / semiperimeter).max(0.0).sqrt();
+    // Transverse directions at each end, in the direction of motion
+    let (mut transverse_1, mut transverse_2) = if unit_normal[2] < 0.0 {
+        lambda = -lambda;
+        (cross(unit_1, unit_normal), cross(unit_2, unit_normal))
+    }
+    else {
+        (cross(unit_normal, unit_1), cross(unit_normal, unit_2))
+    };
+    if !prograde {
+        lambda = -lambda;
+        transverse_1 = scale(transverse_1, -1.0);
+        transverse_2 = scale(transverse_2, -1.0);
+    }
+    let geometry = Geometry { lambda, time: (2.0 * mu / semiperimeter.powi(3)).sqrt() * time_of_flight };
+
+    // Solutions for x, the zero revolution one first
+    let lambda3 = lambda.powi(3);
+    let t00 = lambda.acos() + lambda * (1.0 - lambda * lambda).sqrt();
+    let t1 = 2.0 / 3.0 * (1.0 - lambda3);
+    let t = geometry.time;
+    let guess = if t >= t00 {
+        -(t - t00) / (t - t00 + 4.0)
+    }
+    else if t <= t1 {
+        t1 * (t1 - t) / (0.4 * (1.0 - lambda3 * lambda * lambda) * t) + 1.0
+    }
+    else {
+        (t / t00).powf(std::f64::consts::LN_2 / (t1 / t00).ln()) - 1.0
+    };
+    let mut solutions = vec![(0, LambertBranch::Single, geometry.householder(guess, 0, 1e-5))];
+    for revolutions in 1..=geometry.max_revolutions().min(max_revolutions) {
+        let n = revolutions as f64;
+        let left = ((n * PI + PI) / (8.0 * t)).powf(2.0 / 3.0);
+        let right = ((8.0 * t) / (n * PI)).powf(2.0 / 3.0);
+        solutions.push((revolutions, LambertBranch::Left, geometry.householder((left - 1.0) / (left + 1.0), revolutions, 1e-8)));
+        solutions.push((revolutions, LambertBranch::Right, geometry.householder((right - 1.0) / (right + 1.0), revolutions, 1e-8)));
+    }
+
+    // Back to velocities through their radial and transverse parts
+    let gamma = (mu * semiperimeter / 2.0).sqrt();
+    let rho = (r1 - r2) / chord;
+    let sigma = (1.0 - rho * rho).max(0.0).sqrt();
+    solutions
+        .into_iter()
+        .filter(|(_, _, x)| x.is_finite())
+        .map(|(revolutions, branch, x)| {
+            let y = (1.0 - lambda * lambda + lambda * lambda * x * x).sqrt();
+            let radial_1 = gamma * ((lambda * y - x) - rho * (lambda * y + x)) / r1;
+            let radial_2 = -gamma * ((lambda * y - x) + rho * (lambda * y + x)) / r2;
+            let transverse = gamma * sigma * (y + lambda * x);
+            LambertSolution {
+                revolutions,
+                branch,
+                departure: State { velocity: add(scale(unit_1, radial_1), scale(transverse_1, transverse / r1)), ..departure.clone() },
+                arrival: State { velocity: add(scale(unit_2, radial_2), scale(transverse_2, transverse / r2)), ..arrival.clone() },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frames::Frame;
+    use crate::planet::OrbitalElements;
+    use crate::units::{Mass, Position, Time, Velocity, KM_PER_AU};
+
+    const MU: GravitationalParameter = GravitationalParameter::km3_per_s2(1.32712440018E11);
+
+    /// A heliocentric state at `position` (au) `days` past the epoch, Lambert only looks at the position
+    fn at(position: [f64; 3], days: f64) -> State {
+        State::new(Frame::ecliptic("Sun"), Time::days(days), Position::km(scale(position, KM_PER_AU)), Velocity::default())
+    }
+
+    /// Distance (km) between the arrival position and where the departure state actually ends up after the time of flight
+    fn arrival_miss(solution: &LambertSolution) -> f64 {
+        let (position, _) = OrbitalElements::from_state(&solution.departure, MU, Mass::kg(0.0))
+            .after(MU, solution.arrival.time - solution.departure.time)
+            .to_state_vectors(MU);
+        norm(sub(position, solution.arrival.position))
+    }
+
+    #[test]
+    fn every_solution_reaches_the_arrival_position() {
+        let (departure, arrival) = (at([1.0, 0.0, 0.0], 0.0), at([-0.8, 1.2, 0.1], 900.0));
+        for prograde in [true, false] {
+            let solutions = solve_lambert(&departure, &arrival, MU, prograde, 2);
+            assert!(solutions.len() > 1, "expected multi revolution solutions, got {}", solutions.len());
+            for solution in &solutions {
+                let miss = arrival_miss(solution);
+                assert!(
+                    miss < 1.0,
+                    "missed by {} km with {} revolutions ({:?}), prograde {}",
+                    miss,
+                    solution.revolutions,
+                    solution.branch,
+                    prograde
+                );
+                assert_eq!(solution.arrival.frame, arrival.frame);
+            }
+        }
+    }
+
+    #[test]
+    fn no_solution_without_a_plane_time_or_common_frame() {
+        let departure = at([1.0, 0.0, 0.0], 0.0);
+        assert!(solve_lambert(&departure, &at([2.0, 0.0, 0.0], 100.0), MU, true, 0).is_empty());
+        assert!(solve_lambert(&departure, &at([0.0, 1.0, 0.0], 0.0), MU, true, 0).is_empty());
+        let elsewhere = State { frame: Frame::ecliptic("Earth"), ..at([0.0, 1.0, 0.0], 100.0) };
+        assert!(solve_lambert(&departure, &elsewhere, MU, true, 0).is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::epoch::J2000;
+use crate::error::{LoadError, Location};
+use crate::planet::{SolarSystem, SUN};
+use crate::schema::{BodyRecord, CentralBodyRecord, SystemRecord};
+
+/// A table from the legacy data.toml layout. That layout nests tables by indentation rather than
+/// dotted names and writes thousands with commas (696,340), so it isn't valid TOML and is read by hand
+#[derive(Debug, Default)]
+struct LegacyTable {
+    name: String,
+    line: u32, // of the header, 0 for the top of the file
+    values: BTreeMap<String, f64>,
+    children: Vec<LegacyTable>,
+}
+
+impl LegacyTable {
+    fn child(&self, name: &str) -> Option<&LegacyTable> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+/// Splits the legacy text into nested tables, a header belongs to the closest less indented header above it
+/// and keys belong to the header right above them
+fn parse_tables(contents: &str, path: Option<&Path>) -> Result<LegacyTable, LoadError> {
+    let location = |line: u32| Location { path: path.map(Path::to_path_buf), line: Some(line), column: None };
+    // Tables still open, with the indentation of their header. The top of the file is never closed
+    let mut open: Vec<(Option<usize>, LegacyTable)> = vec![(None, LegacyTable::default())];
+    for (index, raw_line) in contents.lines().enumerate() {
+        let line_number = index as u32 + 1;
+        let line = raw_line.split('#').next().unwrap_or("");
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        if let Some(name) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            // Close every table indented as far or further than this header
+            while open.len() > 1 && open.last().is_some_and(|(open_indent, _)| open_indent.is_some_and(|i| i >= indent)) {
+                let (_, table) = open.pop().expect("checked above");
+                open.last_mut().expect("top is never closed").1.children.push(table);
+            }
+            let table = LegacyTable { name: name.trim().to_string(), line: line_number, ..Default::default() };
+            open.push((Some(indent), table));
+        }
+        else if let Some((key, value)) = text.split_once('=') {
+            let key = key.trim().to_string();
+            let cleaned: String = value.trim().chars().filter(|c| *c != ',' && *c != '_').collect();
+            let number = cleaned.parse::<f64>().map_err(|_| LoadError::InvalidField {
+                body: open.last().filter(|_| open.len() > 1).map(|(_, table)| table.name.clone()),
+                field: key.clone(),
+                reason: format!("expected a number, found {}", value.trim()),
+                location: location(line_number),
+            })?;
+            open.last_mut().expect("top is never closed").1.values.insert(key, number);
+        }
+        else {
+            return Err(LoadError::Syntax {
+                location: location(line_number),
+                message: format!("expected a [table] or key = value, found {}", text),
+            });
+        }
+    }
+    while open.len() > 1 {
+        let (_, table) = open.pop().expect("checked by the loop");
+        open.last_mut().expect("top is never closed").1.children.push(table);
+    }
+    Ok(open.pop().expect("top is never closed").1)
+}
+
+/// Reads a value every legacy body needs
+fn required(table: &LegacyTable, key: &str, path: Option<&Path>) -> Result<f64, LoadError> {
+    table.values.get(key).copied().ok_or_else(|| LoadError::MissingField {
+        body: Some(table.name.clone()),
+        field: key.to_string(),
+        location: Location { path: path.map(Path::to_path_buf), line: Some(table.line), column: None },
+    })
+}
+
+/// A legacy body on a circular orbit in the reference plane at its `distance`,
+/// with its moons (indented under a [moons] table) built the same way around it
+fn body_record(table: &LegacyTable, path: Option<&Path>) -> Result<BodyRecord, LoadError> {
+    let mut moons = BTreeMap::new();
+    if let Some(moon_table) = table.child("moons") {
+        for moon in &moon_table.children {
+            moons.insert(moon.name.clone(), body_record(moon, path)?);
+        }
+    }
+    Ok(BodyRecord {
+        semi_major_axis_km: required(table, "distance", path)?,
+        eccentricity: 0.0,
+        inclination_degrees: 0.0,
+        mean_longitude_degrees: 0.0,
+        longitude_of_perihelion_degrees: 0.0,
+        longitude_of_the_ascending_node_degrees: 0.0,
+        meanradius_km: required(table, "radius", path)?,
+        mass_kg: required(table, "mass", path)?,
+        rates_per_century: None,
+        zonal_harmonics: None,
+        atmosphere: None,
+        spacecraft: None,
+        rotation: None,
+        moons,
+    })
+}
+
+/// Reads the legacy layout into the current schema. The Sun's entry becomes the central body
+/// and every other body gets a circular orbit. path is only used to say where errors are
+pub fn legacy_record(contents: &str, path: Option<&Path>) -> Result<SystemRecord, LoadError> {
+    let top = parse_tables(contents, path)?;
+    let file_location = || Location { path: path.map(Path::to_path_buf), line: None, column: None };
+    let system = top.child("solar_system").ok_or_else(|| LoadError::MissingField {
+        body: None,
+        field: "solar_system".to_string(),
+        location: file_location(),
+    })?;
+    let number_of_bodies = top.values.get("number_of_bodies").copied().ok_or_else(|| LoadError::MissingField {
+        body: None,
+        field: "number_of_bodies".to_string(),
+        location: file_location(),
+    })?;
+
+    let mut central_body = None;
+    let mut bodies = BTreeMap::new();
+    for table in &system.children {
+        if table.name == SUN {
+            central_body = Some(CentralBodyRecord {
+                name: table.name.clone(),
+                mass_kg: required(table, "mass", path)?,
+                meanradius_km: required(table, "radius", path)?,
+                zonal_harmonics: None,
+            });
+        }
+        else {
+            bodies.insert(table.name.clone(), body_record(table, path)?);
+        }
+    }
+    let record = SystemRecord {
+        number_of_bodies: number_of_bodies as usize,
+        epoch_jd: J2000.in_days(),
+        valid_from_jd: None,
+        valid_to_jd: None,
+        central_body,
+        bodies,
+    };
+    record.validate().map_err(|violation| LoadError::InvalidField {
+        body: violation.body,
+        field: violation.field,
+        reason: violation.reason,
+        location: file_location(),
+    })?;
+    Ok(record)
+}
+
+/// Rewrites legacy text in the current celestial_bodies_data.toml schema
+pub fn convert_legacy(contents: &str) -> Result<String, LoadError> {
+    let record = legacy_record(contents, None)?;
+    Ok(toml::to_string(&record).expect("Schema records always serialize"))
+}
+
+/// Converts a legacy file on disk into a current one at `output`
+pub fn convert_legacy_file(input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<(), LoadError> {
+    let input = input.as_ref();
+    let output = output.as_ref();
+    let contents = fs::read_to_string(input).map_err(|source| LoadError::Io { path: input.to_path_buf(), source })?;
+    let record = legacy_record(&contents, Some(input))?;
+    let converted = toml::to_string(&record).expect("Schema records always serialize");
+    fs::write(output, converted).map_err(|source| LoadError::Io { path: output.to_path_buf(), source })
+}
+
+impl SolarSystem {
+    /// Reads a system written in the legacy data.toml layout, see `legacy_record`
+    pub fn from_legacy_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| LoadError::Io { path: path.to_path_buf(), source })?;
+        Ok(SolarSystem::from_record(&legacy_record(&contents, Some(path))?))
+    }
+
+    /// Same as `from_legacy_path` for text already in memory
+    pub fn from_legacy_str(contents: &str) -> Result<Self, LoadError> {
+        Ok(SolarSystem::from_record(&legacy_record(contents, None)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Length;
+    use crate::vector::norm;
+
+    const LEGACY: &str = include_str!("data.toml");
+
+    #[test]
+    fn legacy_file_converts_and_reads_back_unchanged() {
+        let converted = convert_legacy(LEGACY).expect("the shipped legacy file should convert");
+        let record: SystemRecord = toml::from_str(&converted).expect("converted text should be in the current schema");
+        assert_eq!(record.validate(), Ok(()));
+        assert_eq!(toml::to_string(&record).expect("records serialize"), converted);
+
+        let sun = record.central_body.as_ref().expect("the Sun entry becomes the central body");
+        assert_eq!((sun.name.as_str(), sun.meanradius_km), (SUN, 696340.0));
+        assert_eq!(record.bodies.len(), record.number_of_bodies);
+        assert_eq!(record.bodies["Earth"].moons["the_moon"].semi_major_axis_km, 384400.0);
+    }
+
+    #[test]
+    fn legacy_bodies_start_on_circular_orbits_at_their_distance() {
+        let system = SolarSystem::from_legacy_str(LEGACY).expect("the shipped legacy file should load");
+        for (name, distance) in [("Earth", 149600000.0), ("the_moon", 384400.0)] {
+            let radius = Length::km(norm(system.state(name, 0).expect("body should be in the system").position));
+            assert!((radius - Length::km(distance)).abs() < Length::km(1E-6), "{} starts at {}", name, radius);
+        }
+    }
+
+    #[test]
+    fn malformed_values_point_at_their_line() {
+        let error = legacy_record(&LEGACY.replace("radius = 6052", "radius = big"), None).expect_err("radius isn't a number");
+        let LoadError::InvalidField { body, field, location, .. } = &error else { panic!("expected an invalid field, got {:?}", error) };
+        assert_eq!((body.as_deref(), field.as_str(), location.line), (Some("Venus"), "radius", Some(13)));
+    }
+}
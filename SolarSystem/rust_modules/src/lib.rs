@@ -0,0 +1,26 @@
+pub mod conservation;
+pub mod eclipses;
+pub mod encounters;
+pub mod epoch;
+pub mod error;
+pub mod export;
+pub mod frames;
+pub mod harmonics;
+pub mod horizons;
+pub mod integrators;
+pub mod jpl_import;
+pub mod kepler;
+pub mod lambert;
+pub mod legacy;
+pub mod non_gravitational;
+pub mod orbit_propagration;
+pub mod patched_conic;
+pub mod phenomena;
+pub mod planet;
+pub mod porkchop;
+pub mod relativity;
+pub mod schema;
+pub mod summary;
+pub mod transfer;
+pub mod units;
+pub mod vector;
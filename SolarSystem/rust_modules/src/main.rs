@@ -1,9 +1,28 @@
-use planet::setup_from_toml;
+use std::process::exit;
 
-
-
-mod planet;
+use solar_system::{
+    orbit_propagration::propagate_system_two_body,
+    planet::{SolarSystem, DEFAULT_DATA_PATH},
+    units::Time,
+};
 
 fn main() {
-    let system = setup_from_toml();
+    let mut system = SolarSystem::from_path(DEFAULT_DATA_PATH).unwrap_or_else(|e| {
+        eprintln!("Could not load the solar system: {}", e);
+        exit(1);
+    });
+    // One year in daily steps
+    propagate_system_two_body(&mut system, Time::years(1.0), Time::days(1.0));
+    let last = system.sample_count() - 1;
+    for name in system.bodies.keys() {
+        let state = system.heliocentric_state(name, last).unwrap();
+        let frame = state.frame();
+        println!(
+            "{}: position {:?} km, velocity {:?} km/s after {} days",
+            name,
+            state.position_in(frame).unwrap().in_km(),
+            state.velocity_in(frame).unwrap().in_km_per_s(),
+            last
+        );
+    }
 }
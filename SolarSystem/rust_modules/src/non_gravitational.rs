@@ -0,0 +1,195 @@
+use crate::schema::{AtmosphereRecord, SpacecraftRecord};
+use crate::units::{Acceleration, Area, Density, Length, Mass, Position, Velocity, KM_PER_AU, M_PER_KM};
+use crate::vector::{dot, norm, scale};
+
+/// Solar radiation pressure at one astronomical unit, N/m^2
+const SOLAR_PRESSURE_AT_AU: f64 = 4.56E-6;
+
+/// What a satellite presents to sunlight and to an atmosphere, its mass comes from its orbital data
+#[derive(Debug, Clone)]
+pub struct SpacecraftProperties {
+    pub area: Area, // cross section
+    pub reflectivity_coefficient: f64, // none, 1 absorbs everything and 2 reflects everything
+    pub drag_coefficient: f64, // none
+}
+
+impl SpacecraftProperties {
+    /// From the data file
+    pub fn from_record(record: &SpacecraftRecord) -> Self {
+        Self {
+            area: Area::m2(record.area_m2),
+            reflectivity_coefficient: record.reflectivity_coefficient,
+            drag_coefficient: record.drag_coefficient,
+        }
+    }
+
+    /// Cannonball radiation pressure acceleration on a satellite of `mass` at `from_sun` from the Sun,
+    /// scaled by the fraction of the Sun's disc that is visible (see `sunlit_fraction`)
+    pub fn radiation_pressure(&self, mass: Mass, from_sun: Position, sunlit_fraction: f64) -> Acceleration {
+        let from_sun = from_sun.in_km();
+        let distance = norm(from_sun);
+        let pressure = SOLAR_PRESSURE_AT_AU * (KM_PER_AU / distance).powi(2);
+        let magnitude = sunlit_fraction * pressure * self.reflectivity_coefficient * self.area.in_m2() / mass.in_kg() / M_PER_KM;
+        Acceleration::km_per_s2(scale(from_sun, magnitude / distance))
+    }
+
+    /// Drag acceleration on a satellite of `mass` moving at `velocity` through air of `density`,
+    /// the velocity being relative to the air
+    pub fn drag(&self, mass: Mass, density: Density, velocity: Velocity) -> Acceleration {
+        let velocity = velocity.in_km_per_s();
+        let speed = norm(velocity);
+        // 1/2 rho Cd A/m v^2 in m/s^2, with the speed converted from km/s and the result back to km/s^2
+        let factor = -0.5 * density.in_kg_per_m3() * self.drag_coefficient * self.area.in_m2() / mass.in_kg() * speed * M_PER_KM;
+        Acceleration::km_per_s2(scale(velocity, factor))
+    }
+}
+
+/// Fraction (0 to 1) of the Sun's disc seen from a satellite that isn't hidden by the planet it orbits,
+/// treating both as spheres. to_sun and to_planet point from the satellite at each centre
+pub fn sunlit_fraction(to_sun: Position, sun_radius: Length, to_planet: Position, planet_radius: Length) -> f64 {
+    let (to_sun, to_planet) = (to_sun.in_km(), to_planet.in_km());
+    let (sun_radius, planet_radius) = (sun_radius.in_km(), planet_radius.in_km());
+    let (sun_distance, planet_distance) = (norm(to_sun), norm(to_planet));
+    if planet_distance <= planet_radius {
+        return 0.0;
+    }
+    // Apparent radii of the two discs and the angle between their centres
+    let a = (sun_radius / sun_distance).clamp(-1.0, 1.0).asin();
+    let b = (planet_radius / planet_distance).clamp(-1.0, 1.0).asin();
+    let c = (dot(to_sun, to_planet) / (sun_distance * planet_distance)).clamp(-1.0, 1.0).acos();
+    if c >= a + b {
+        return 1.0;
+    }
+    if c <= b - a {
+        // Umbra
+        return 0.0;
+    }
+    if c <= a - b {
+        // The planet sits wholly inside the Sun's disc
+        return 1.0 - (b * b) / (a * a);
+    }
+    // Penumbra, the area where the two discs overlap
+    let x = (c * c + a * a - b * b) / (2.0 * c);
+    let y = (a * a - x * x).max(0.0).sqrt();
+    let overlap = a * a * (x / a).clamp(-1.0, 1.0).acos() + b * b * ((c - x) / b).clamp(-1.0, 1.0).acos() - c * y;
+    (1.0 - overlap / (std::f64::consts::PI * a * a)).clamp(0.0, 1.0)
+}
+
+/// Density of a planet's atmosphere by altitude above its mean radius. The air is taken not to rotate with the planet
+#[derive(Debug, Clone)]
+pub enum Atmosphere {
+    /// Density falling off exponentially from a reference altitude
+    Exponential {
+        reference_altitude: Length,
+        reference_density: Density,
+        scale_height: Length,
+    },
+    /// Densities at increasing altitudes, interpolated exponentially between them and zero above the top
+    Tabulated {
+        altitudes: Vec<Length>,
+        densities: Vec<Density>,
+    },
+}
+
+impl Atmosphere {
+    /// From a record that has passed validation, so it holds exactly one of the two forms
+    pub fn from_record(record: &AtmosphereRecord) -> Self {
+        match (record.scale_height_km, &record.altitudes_km, &record.densities_kg_m3) {
+            (Some(scale_height), _, _) => Atmosphere::Exponential {
+                reference_altitude: Length::km(record.reference_altitude_km.unwrap_or(0.0)),
+                reference_density: Density::kg_per_m3(record.reference_density_kg_m3.unwrap_or(0.0)),
+                scale_height: Length::km(scale_height),
+            },
+            (None, altitudes, densities) => Atmosphere::Tabulated {
+                altitudes: altitudes.iter().flatten().copied().map(Length::km).collect(),
+                densities: densities.iter().flatten().copied().map(Density::kg_per_m3).collect(),
+            },
+        }
+    }
+
+    /// Density at an altitude
+    pub fn density(&self, altitude: Length) -> Density {
+        match self {
+            Atmosphere::Exponential { reference_altitude, reference_density, scale_height } => {
+                *reference_density * (-((altitude - *reference_altitude) / *scale_height)).exp()
+            }
+            Atmosphere::Tabulated { altitudes, densities } => {
+                let (Some(first), Some(last)) = (altitudes.first(), altitudes.last()) else { return Density::default() };
+                if altitude > *last {
+                    return Density::default();
+                }
+                if altitude <= *first {
+                    return densities[0];
+                }
+                let above = altitudes.partition_point(|table_altitude| *table_altitude < altitude);
+                let below = above - 1;
+                let fraction = (altitude - altitudes[below]) / (altitudes[above] - altitudes[below]);
+                densities[below] * (densities[above] / densities[below]).powf(fraction)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACECRAFT: SpacecraftProperties =
+        SpacecraftProperties { area: Area::km2(10E-6), reflectivity_coefficient: 1.5, drag_coefficient: 2.2 };
+    const MASS: Mass = Mass::kg(1000.0);
+
+    fn close(value: f64, expected: f64) -> bool {
+        ((value - expected) / expected).abs() < 1E-12
+    }
+
+    #[test]
+    fn radiation_pressure_pushes_away_from_the_sun_falling_with_distance() {
+        // P Cr A/m = 4.56E-6 * 1.5 * 10 / 1000 m/s^2 at one au
+        let at_au = SPACECRAFT.radiation_pressure(MASS, Position::km([KM_PER_AU, 0.0, 0.0]), 1.0).in_m_per_s2();
+        assert!(close(at_au[0], 6.84E-8) && at_au[1] == 0.0 && at_au[2] == 0.0, "{:?}", at_au);
+        let further = SPACECRAFT.radiation_pressure(MASS, Position::km([0.0, -2.0 * KM_PER_AU, 0.0]), 0.5).in_m_per_s2();
+        assert!(close(further[1], -6.84E-8 / 8.0), "half lit at two au gets an eighth, {:?}", further);
+    }
+
+    #[test]
+    fn drag_opposes_the_velocity_with_its_square() {
+        // 1/2 rho Cd A/m v^2 = 0.5 * 1E-12 * 2.2 * 10 / 1000 * 7500^2 m/s^2
+        let drag = SPACECRAFT.drag(MASS, Density::kg_per_m3(1E-12), Velocity::km_per_s([0.0, 0.0, 7.5])).in_m_per_s2();
+        assert!(close(drag[2], -6.1875E-7) && drag[0] == 0.0 && drag[1] == 0.0, "{:?}", drag);
+    }
+
+    #[test]
+    fn shadow_goes_from_sunlit_through_penumbra_to_umbra() {
+        let (sun, sun_radius) = (Position::km([KM_PER_AU, 0.0, 0.0]), Length::km(695700.0));
+        let earth_radius = Length::km(6371.0);
+        // The satellite 7000 km from the Earth's centre on the night side, moved off the Sun-Earth line by `offset` km
+        let fraction = |offset: f64| sunlit_fraction(sun, sun_radius, Position::km([7000.0, -offset, 0.0]), earth_radius);
+        assert_eq!(sunlit_fraction(sun, sun_radius, Position::km([-7000.0, 0.0, 0.0]), earth_radius), 1.0, "day side");
+        assert_eq!(fraction(0.0), 0.0, "umbra");
+        assert_eq!(fraction(7000.0), 1.0, "clear of the Earth");
+        let mut previous = 0.0;
+        for offset in (6280..6420).step_by(10) {
+            let lit = fraction(offset as f64);
+            assert!((0.0..=1.0).contains(&lit) && lit >= previous, "{} lit at {} km, {} before", lit, offset, previous);
+            previous = lit;
+        }
+        assert!(previous == 1.0 && fraction(6350.0) > 0.0 && fraction(6350.0) < 1.0, "penumbra around the Earth's limb");
+    }
+
+    #[test]
+    fn atmospheres_fall_off_exponentially() {
+        let exponential = Atmosphere::Exponential {
+            reference_altitude: Length::km(400.0),
+            reference_density: Density::kg_per_m3(3E-12),
+            scale_height: Length::km(60.0),
+        };
+        assert!(close(exponential.density(Length::km(460.0)).in_kg_per_m3(), 3E-12 / std::f64::consts::E));
+        let tabulated = Atmosphere::Tabulated {
+            altitudes: vec![Length::km(200.0), Length::km(400.0)],
+            densities: vec![Density::kg_per_m3(2.5E-10), Density::kg_per_m3(2.5E-12)],
+        };
+        assert!(close(tabulated.density(Length::km(300.0)).in_kg_per_m3(), 2.5E-11), "geometric mean halfway");
+        assert_eq!(tabulated.density(Length::km(100.0)), Density::kg_per_m3(2.5E-10));
+        assert_eq!(tabulated.density(Length::km(401.0)), Density::default());
+    }
+}
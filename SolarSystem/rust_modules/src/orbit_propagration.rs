@@ -1,8 +1,281 @@
+use std::collections::HashMap;
 
-const GRAVITATIONAL_CONSTANT: f64 = 6.6743E-11;
-const MASS_OF_SUN: f64 = 1.99E30;
+use crate::harmonics::ZonalHarmonics;
+use crate::integrators::Integrator;
+use crate::non_gravitational::{sunlit_fraction, Atmosphere, SpacecraftProperties};
+use crate::planet::{Body, OrbitalElements, SolarSystem};
+use crate::relativity::schwarzschild_acceleration;
+use crate::units::{GravitationalParameter, Length, Mass, Position, Time, Velocity};
+use crate::vector::{add, norm, scale, sub};
 
-pub fn get_mu(body1: Body, body2: Body) -> f64{
-    GRAVITATIONAL_CONSTANT*(body1::mass as f64 + body2::mass as f64)
+pub const MASS_OF_SUN: Mass = Mass::kg(1.989E30);
+pub const RADIUS_OF_SUN: Length = Length::km(695700.0);
+
+/// Gravitational parameter of a mass
+pub fn gravitational_parameter(mass: Mass) -> GravitationalParameter {
+    GravitationalParameter::from(mass)
+}
+
+/// Gravitational parameter of a body orbiting a central mass
+pub fn get_mu(central_mass: Mass, body: &Body) -> GravitationalParameter {
+    gravitational_parameter(central_mass + body.orbit_data.mass)
+}
+
+/// Offsets from the start of a run at which states are recorded,
+/// every `step` up to `time_span` plus a final shorter step if the span isn't a multiple of the step.
+/// Empty unless the step is positive, so a run given a bad step records nothing
+pub(crate) fn output_offsets(time_span: Time, step: Time) -> Vec<Time> {
+    if step.in_seconds().is_nan() || step <= Time::default() {
+        return Vec::new();
+    }
+    let full_steps = (time_span / step).floor() as usize;
+    let mut offsets: Vec<Time> = (1..=full_steps).map(|i| i as f64 * step).collect();
+    if time_span - full_steps as f64 * step > 1E-9 * step {
+        offsets.push(time_span);
+    }
+    offsets
 }
 
+/// Analytic two-body propagation of `body` around a central mass.
+/// Starting from the body's last stored state, a state is appended to its history every `step`
+/// until `time_span` has passed, with a final shorter step if the span isn't a multiple of the step.
+/// If the central body has zonal harmonics the orbit's node, perigee and mean anomaly drift at their J2 secular rates.
+/// Moons are carried along around this body in the same way, their states stay relative to it.
+/// Nothing is recorded unless `step` is positive
+pub fn propagate_two_body(body: &mut Body, central_mass: Mass, central_harmonics: Option<&ZonalHarmonics>, time_span: Time, step: Time) {
+    let mu = get_mu(central_mass, body);
+    let position = *body.coords.last().expect("Body has no starting position");
+    let velocity = *body.vel.last().expect("Body has no starting velocity");
+    let start_time = body.times.last().copied().unwrap_or_default();
+
+    // Elements of the osculating orbit, only the mean anomaly (and the secular drift) changes from here on
+    let elements = OrbitalElements::from_state_vectors(position, velocity, mu, body.orbit_data.mass);
+
+    for offset in output_offsets(time_span, step) {
+        let moved = match central_harmonics {
+            Some(harmonics) => harmonics.advance(&elements, mu, offset),
+            None => elements.after(mu, offset),
+        };
+        let (position, velocity) = moved.to_state_vectors(mu);
+        body.coords.push(position);
+        body.vel.push(velocity);
+        body.times.push(start_time + offset);
+    }
+
+    let mass = body.orbit_data.mass;
+    if let Some(moons) = body.moons.as_mut() {
+        for moon in moons.values_mut() {
+            propagate_two_body(moon, mass, body.zonal_harmonics.as_ref(), time_span, step);
+        }
+    }
+}
+
+/// Analytic two-body propagation of every body in the system around the Sun (or whatever the central body is), with each moon propagated
+/// in its parent's frame, see `propagate_two_body`. Heliocentric states of moons come from `SolarSystem::heliocentric_state`
+pub fn propagate_system_two_body(system: &mut SolarSystem, time_span: Time, step: Time) {
+    let central = &system.central_body;
+    for body in system.bodies.values_mut() {
+        propagate_two_body(body, central.mass, central.zonal_harmonics.as_ref(), time_span, step);
+    }
+}
+
+/// Newtonian gravitational acceleration (km/s^2) on every body from every other one, positions in km
+pub fn gravitational_accelerations(masses: &[Mass], positions: &[[f64; 3]]) -> Vec<[f64; 3]> {
+    let mus: Vec<f64> = masses.iter().map(|mass| gravitational_parameter(*mass).in_km3_per_s2()).collect();
+    let mut accelerations = vec![[0.0; 3]; positions.len()];
+    for i in 0..positions.len() {
+        for j in (i + 1)..positions.len() {
+            let separation = sub(positions[j], positions[i]);
+            let distance_squared = separation.iter().map(|x| x * x).sum::<f64>();
+            let inverse_cube = 1.0 / (distance_squared * distance_squared.sqrt());
+            for k in 0..3 {
+                accelerations[i][k] += mus[j] * separation[k] * inverse_cube;
+                accelerations[j][k] -= mus[i] * separation[k] * inverse_cube;
+            }
+        }
+    }
+    accelerations
+}
+
+/// Acceleration (km/s^2) on every body from the zonal harmonics of the others' fields, see `ZonalHarmonics::acceleration`.
+/// Each body with a field is pulled back the other way so momentum is conserved. positions in km
+pub fn zonal_accelerations(masses: &[Mass], positions: &[[f64; 3]], fields: &[Option<&ZonalHarmonics>]) -> Vec<[f64; 3]> {
+    let mut accelerations = vec![[0.0; 3]; positions.len()];
+    for (source, field) in fields.iter().enumerate() {
+        let Some(field) = field else { continue };
+        for target in (0..positions.len()).filter(|target| *target != source) {
+            let acceleration = field
+                .acceleration(gravitational_parameter(masses[source]), Position::km(sub(positions[target], positions[source])))
+                .in_km_per_s2();
+            accelerations[target] = add(accelerations[target], acceleration);
+            accelerations[source] = sub(accelerations[source], scale(acceleration, masses[target] / masses[source]));
+        }
+    }
+    accelerations
+}
+
+/// Optional terms added to the N-body equations of motion on top of point mass gravity
+/// and the zonal harmonics given in the body data
+#[derive(Debug, Clone, Default)]
+pub struct ForceModel {
+    pub relativity: bool, // first order post-Newtonian correction from the central body
+    pub solar_radiation_pressure: bool, // on bodies with spacecraft properties, shadowed by their parent
+    pub drag: bool, // on bodies with spacecraft properties whose parent has an atmosphere
+}
+
+/// One body of the system flattened out of the moon hierarchy for N-body propagation
+struct FlatBody {
+    mass: Mass,
+    radius: Length,
+    zonal_harmonics: Option<ZonalHarmonics>,
+    atmosphere: Option<Atmosphere>,
+    spacecraft: Option<SpacecraftProperties>,
+    parent: Option<usize>, // index into the flattened list, None for bodies orbiting the Sun
+    position: [f64; 3], // km, absolute
+    velocity: [f64; 3], // km/s, absolute
+}
+
+/// Depth first walk of the bodies and their moons, converting the parent relative states to absolute ones
+fn flatten(bodies: &HashMap<String, Body>, parent: Option<usize>, flat: &mut Vec<FlatBody>) {
+    for body in bodies.values() {
+        let (parent_position, parent_velocity) = match parent {
+            Some(index) => (flat[index].position, flat[index].velocity),
+            None => ([0.0; 3], [0.0; 3]),
+        };
+        flat.push(FlatBody {
+            mass: body.orbit_data.mass,
+            radius: body.radius,
+            zonal_harmonics: body.zonal_harmonics.clone(),
+            atmosphere: body.atmosphere.clone(),
+            spacecraft: body.spacecraft.clone(),
+            parent,
+            position: add(parent_position, *body.coords.last().expect("Body has no starting position")),
+            velocity: add(parent_velocity, *body.vel.last().expect("Body has no starting velocity")),
+        });
+        if let Some(moons) = body.moons.as_ref() {
+            let index = flat.len() - 1;
+            flatten(moons, Some(index), flat);
+        }
+    }
+}
+
+/// Same walk as `flatten`, appending each body's new state relative to its parent (or the Sun)
+fn record(bodies: &mut HashMap<String, Body>, relative: &[([f64; 3], [f64; 3])], time: Time, index: &mut usize) {
+    for body in bodies.values_mut() {
+        let (position, velocity) = relative[*index];
+        body.coords.push(position);
+        body.vel.push(velocity);
+        body.times.push(time);
+        *index += 1;
+        if let Some(moons) = body.moons.as_mut() {
+            record(moons, relative, time, index);
+        }
+    }
+}
+
+/// Propagates every body (moons included) under their mutual gravity and the Sun's with the given integrator,
+/// including the zonal harmonics of any body (or the Sun) that has them and the optional terms in `forces`.
+/// Starting from the last stored states, a state is appended to each body every `step` until
+/// `time_span` has passed. States stay heliocentric for planets and relative to the parent for moons.
+/// Nothing is recorded unless `step` is positive
+pub fn propagate_n_body(system: &mut SolarSystem, integrator: &mut dyn Integrator, forces: &ForceModel, time_span: Time, step: Time) {
+    integrate_n_body(system, integrator, forces, time_span, step, |_, _, _, _| {});
+}
+
+/// `propagate_n_body`, also handing `observe` the time, masses and absolute positions (km) and velocities (km/s)
+/// of the Sun (first) and every body at the start and after each step. The Sun starts at rest at the origin
+pub(crate) fn integrate_n_body(
+    system: &mut SolarSystem,
+    integrator: &mut dyn Integrator,
+    forces: &ForceModel,
+    time_span: Time,
+    step: Time,
+    mut observe: impl FnMut(Time, &[Mass], &[[f64; 3]], &[[f64; 3]]),
+) {
+    let mut flat = Vec::new();
+    flatten(&system.bodies, None, &mut flat);
+    let start_time = system.bodies.values().next().and_then(|body| body.times.last().copied()).unwrap_or_default();
+
+    // The Sun goes first and starts at the origin, the rest of the indices shift by one
+    let mut masses = vec![system.central_body.mass];
+    let mut positions = vec![[0.0; 3]];
+    let mut velocities = vec![[0.0; 3]];
+    let mut fields = vec![system.central_body.zonal_harmonics.as_ref()];
+    for body in &flat {
+        masses.push(body.mass);
+        positions.push(body.position);
+        velocities.push(body.velocity);
+        fields.push(body.zonal_harmonics.as_ref());
+    }
+    let has_fields = fields.iter().any(Option::is_some);
+    let central_mu = gravitational_parameter(system.central_body.mass);
+    let central_radius = system.central_body.radius;
+    let acceleration = |_time: Time, positions: &[[f64; 3]], velocities: &[[f64; 3]]| {
+        let mut accelerations = gravitational_accelerations(&masses, positions);
+        if has_fields {
+            for (total, zonal) in accelerations.iter_mut().zip(zonal_accelerations(&masses, positions, &fields)) {
+                *total = add(*total, zonal);
+            }
+        }
+        if forces.relativity {
+            // Every body feels the correction relative to the Sun, which is pulled back the other way
+            for i in 1..positions.len() {
+                let position = Position::km(sub(positions[i], positions[0]));
+                let velocity = Velocity::km_per_s(sub(velocities[i], velocities[0]));
+                let correction = schwarzschild_acceleration(central_mu, position, velocity).in_km_per_s2();
+                accelerations[i] = add(accelerations[i], correction);
+                accelerations[0] = sub(accelerations[0], scale(correction, masses[i] / masses[0]));
+            }
+        }
+        if forces.solar_radiation_pressure || forces.drag {
+            for (i, body) in flat.iter().enumerate() {
+                let Some(spacecraft) = body.spacecraft.as_ref() else { continue };
+                let index = i + 1;
+                let parent = body.parent.map(|parent| (parent + 1, &flat[parent]));
+                if forces.solar_radiation_pressure {
+                    let fraction = parent.map_or(1.0, |(parent_index, parent)| {
+                        sunlit_fraction(
+                            Position::km(sub(positions[0], positions[index])),
+                            central_radius,
+                            Position::km(sub(positions[parent_index], positions[index])),
+                            parent.radius,
+                        )
+                    });
+                    let from_sun = Position::km(sub(positions[index], positions[0]));
+                    let pressure = spacecraft.radiation_pressure(body.mass, from_sun, fraction).in_km_per_s2();
+                    accelerations[index] = add(accelerations[index], pressure);
+                }
+                if let (true, Some((parent_index, parent))) = (forces.drag, parent) {
+                    if let Some(atmosphere) = parent.atmosphere.as_ref() {
+                        let altitude = Length::km(norm(sub(positions[index], positions[parent_index]))) - parent.radius;
+                        let density = atmosphere.density(altitude);
+                        let relative_velocity = Velocity::km_per_s(sub(velocities[index], velocities[parent_index]));
+                        let drag = spacecraft.drag(body.mass, density, relative_velocity).in_km_per_s2();
+                        accelerations[index] = add(accelerations[index], drag);
+                    }
+                }
+            }
+        }
+        accelerations
+    };
+
+    observe(start_time, &masses, &positions, &velocities);
+    let mut time = start_time;
+    for offset in output_offsets(time_span, step) {
+        let target = start_time + offset;
+        while target - time > 1E-9 * step {
+            time += integrator.step(time, target - time, &mut positions, &mut velocities, &acceleration);
+        }
+        time = target;
+        let relative: Vec<([f64; 3], [f64; 3])> = flat
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let centre = body.parent.map_or(0, |parent| parent + 1);
+                (sub(positions[i + 1], positions[centre]), sub(velocities[i + 1], velocities[centre]))
+            })
+            .collect();
+        record(&mut system.bodies, &relative, time, &mut 0);
+        observe(time, &masses, &positions, &velocities);
+    }
+}
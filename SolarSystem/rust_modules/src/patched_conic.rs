@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::frames::{Frame, State};
+use crate::orbit_propagration::gravitational_parameter;
+use crate::planet::{Body, OrbitalElements, SolarSystem};
+use crate::units::{JulianDate, Length, Mass, Time};
+use crate::vector::{add, norm, sub};
+
+/// Crossing times are refined until they're known to this
+const CROSSING_TOLERANCE: Time = Time::seconds(1E-3);
+const MAX_BISECTIONS: usize = 100;
+
+/// Sizes of the regions around a body where its gravity matters most
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfluenceSpheres {
+    pub laplace: Length, // a (m/M)^(2/5), where patched conics switch to this body
+    pub hill: Length, // a (1-e) (m/3M)^(1/3), where this body can hold on to satellites
+}
+
+impl InfluenceSpheres {
+    /// Spheres of a body of `mass` on the given orbit around a central mass
+    pub fn new(elements: &OrbitalElements, mass: Mass, central_mass: Mass) -> Self {
+        let a = elements.semimajor_axis.abs();
+        Self {
+            laplace: a * (mass / central_mass).powf(0.4),
+            hill: a * (1.0 - elements.eccentricity).max(0.0) * (mass / (3.0 * central_mass)).cbrt(),
+        }
+    }
+}
+
+impl SolarSystem {
+    /// Laplace and Hill spheres of a body or moon from its elements at the epoch, None if it isn't in the system
+    pub fn influence_spheres(&self, name: &str) -> Option<InfluenceSpheres> {
+        let body = self.find(name)?;
+        let (central_mass, _) = self.parent_of(name)?;
+        Some(InfluenceSpheres::new(&body.orbit_data, body.orbit_data.mass, central_mass))
+    }
+
+    /// `influence_spheres` of every body and moon in the system, by name
+    pub fn all_influence_spheres(&self) -> BTreeMap<String, InfluenceSpheres> {
+        fn collect(system: &SolarSystem, bodies: &HashMap<String, Body>, spheres: &mut BTreeMap<String, InfluenceSpheres>) {
+            for (name, body) in bodies {
+                if let Some(sphere) = system.influence_spheres(name) {
+                    spheres.insert(name.clone(), sphere);
+                }
+                if let Some(moons) = body.moons.as_ref() {
+                    collect(system, moons, spheres);
+                }
+            }
+        }
+        let mut spheres = BTreeMap::new();
+        collect(self, &self.bodies, &mut spheres);
+        spheres
+    }
+
+    /// Names of the bodies that orbit `name` directly, the planets for the central body
+    fn children(&self, name: &str) -> Vec<String> {
+        if name == self.central_body.name {
+            return self.bodies.keys().cloned().collect();
+        }
+        self.find(name)
+            .and_then(|body| body.moons.as_ref())
+            .map_or_else(Vec::new, |moons| moons.keys().cloned().collect())
+    }
+
+    /// Name of whatever the named body orbits, the central body for planets
+    pub(crate) fn parent_name(&self, name: &str) -> Option<String> {
+        fn search(bodies: &HashMap<String, Body>, name: &str) -> Option<String> {
+            for (body_name, body) in bodies {
+                let Some(moons) = body.moons.as_ref() else { continue };
+                if moons.contains_key(name) {
+                    return Some(body_name.clone());
+                }
+                if let Some(parent) = search(moons, name) {
+                    return Some(parent);
+                }
+            }
+            None
+        }
+        if self.bodies.contains_key(name) {
+            return Some(self.central_body.name.clone());
+        }
+        search(&self.bodies, name)
+    }
+
+    /// Mass of a body or of the central body
+    fn mass_of(&self, name: &str) -> Option<Mass> {
+        if name == self.central_body.name {
+            return Some(self.central_body.mass);
+        }
+        self.find(name).map(|body| body.orbit_data.mass)
+    }
+}
+
+/// The body a probe state is relative to, the centre of its ecliptic frame
+fn central(state: &State) -> &str {
+    state.frame.center().unwrap_or_default()
+}
+
+/// Whether the probe left its central body's sphere of influence or entered one of its children's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoiCrossing {
+    Exit,
+    Entry,
+}
+
+/// The probe switching central body, with its state relative to the new one
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoiEvent {
+    pub crossing: SoiCrossing,
+    pub julian_date: JulianDate,
+    pub from: String,
+    pub state: State, // in the ecliptic frame of the new central body
+}
+
+/// Sampled path of a probe and every change of central body along it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchedConicTrajectory {
+    pub samples: Vec<State>, // each in the ecliptic frame of the central body at the time
+    pub events: Vec<SoiEvent>,
+}
+
+/// The probe state moved on along its conic to `time`, None if its central body isn't in the system
+fn coast(system: &SolarSystem, state: &State, time: Time) -> Option<State> {
+    let mu = gravitational_parameter(system.mass_of(central(state))?);
+    let elements = OrbitalElements::from_state_vectors(state.position, state.velocity, mu, Mass::default());
+    let (position, velocity) = elements.after(mu, time - state.time).to_state_vectors(mu);
+    Some(State { frame: state.frame.clone(), time, position, velocity })
+}
+
+/// A sphere of influence boundary the probe could cross while coasting around its central body
+enum Boundary {
+    Exit { radius: Length, parent: String },
+    Entry { radius: Length, child: String },
+}
+
+impl Boundary {
+    /// Distance (km) past the boundary, negative inside the probe's current sphere of influence
+    fn distance(&self, system: &SolarSystem, state: &State) -> f64 {
+        match self {
+            Boundary::Exit { radius, .. } => norm(state.position) - radius.in_km(),
+            Boundary::Entry { radius, child } => {
+                let child_position = system.ephemeris(child, state.time).map_or([f64::INFINITY; 3], |child| child.position);
+                radius.in_km() - norm(sub(state.position, child_position))
+            }
+        }
+    }
+
+    /// The probe's state relative to the body on the other side of the boundary
+    fn switch(&self, system: &SolarSystem, state: &State) -> Option<SoiEvent> {
+        let (crossing, new_central, position, velocity) = match self {
+            Boundary::Exit { parent, .. } => {
+                let (central_position, central_velocity) = system.ephemeris(central(state), state.time)?.vectors();
+                (SoiCrossing::Exit, parent, add(state.position, central_position), add(state.velocity, central_velocity))
+            }
+            Boundary::Entry { child, .. } => {
+                let (child_position, child_velocity) = system.ephemeris(child, state.time)?.vectors();
+                (SoiCrossing::Entry, child, sub(state.position, child_position), sub(state.velocity, child_velocity))
+            }
+        };
+        Some(SoiEvent {
+            crossing,
+            julian_date: system.epoch + state.time,
+            from: central(state).to_string(),
+            state: State { frame: Frame::ecliptic(new_central), time: state.time, position, velocity },
+        })
+    }
+}
+
+/// Boundaries around the probe's current central body: its own sphere of influence (none for the system's central body)
+/// and those of the bodies orbiting it
+fn boundaries(system: &SolarSystem, central: &str) -> Vec<Boundary> {
+    let mut boundaries = Vec::new();
+    if central != system.central_body.name {
+        if let (Some(spheres), Some(parent)) = (system.influence_spheres(central), system.parent_name(central)) {
+            boundaries.push(Boundary::Exit { radius: spheres.laplace, parent });
+        }
+    }
+    for child in system.children(central) {
+        if let Some(spheres) = system.influence_spheres(&child) {
+            boundaries.push(Boundary::Entry { radius: spheres.laplace, child });
+        }
+    }
+    boundaries
+}
+
+/// Propagates a probe on patched conics: it coasts on a Keplerian orbit around its central body, switching to the parent
+/// when it leaves the central body's Laplace sphere of influence and to a child body when it enters the child's.
+/// A sample is kept every `step` until `time_span` has passed, and each crossing is found to within
+/// a millisecond by bisection and recorded as an event. The step should be short next to the time taken to cross the
+/// smallest sphere on the way, or a flyby can fall between two steps. Bodies follow their elements (see `SolarSystem::ephemeris`).
+/// The probe starts out around the body at the centre of its state's frame, and every state is given in the ecliptic
+/// frame of the central body at the time. None if that body isn't in the system, the state is in a synodic frame
+/// or the step isn't positive
+pub fn propagate_patched_conic(system: &SolarSystem, probe: State, time_span: Time, step: Time) -> Option<PatchedConicTrajectory> {
+    if step.in_seconds().is_nan() || step <= Time::default() {
+        return None;
+    }
+    let start = probe.frame.center()?;
+    system.mass_of(start)?;
+    let end = probe.time + time_span;
+    let mut state = probe.to_frame(system, &Frame::ecliptic(start))?;
+    let mut samples = vec![state.clone()];
+    let mut events = Vec::new();
+    while state.time < end {
+        let boundaries = boundaries(system, central(&state));
+        let next_time = (state.time + step).min(end);
+        let next = coast(system, &state, next_time)?;
+        // Every boundary crossed during this step, the first one is taken
+        let mut crossed = Vec::new();
+        for boundary in boundaries.iter().filter(|boundary| boundary.distance(system, &next) > 0.0) {
+            let (mut inside, mut outside) = (state.time, next_time);
+            for _ in 0..MAX_BISECTIONS {
+                if outside - inside < CROSSING_TOLERANCE {
+                    break;
+                }
+                let middle = 0.5 * (inside + outside);
+                if boundary.distance(system, &coast(system, &state, middle)?) > 0.0 {
+                    outside = middle;
+                }
+                else {
+                    inside = middle;
+                }
+            }
+            crossed.push((outside, boundary));
+        }
+        match crossed.into_iter().min_by(|a, b| a.0.in_seconds().total_cmp(&b.0.in_seconds())) {
+            Some((time, boundary)) => {
+                let event = boundary.switch(system, &coast(system, &state, time)?)?;
+                state = event.state.clone();
+                samples.push(state.clone());
+                events.push(event);
+            }
+            None => {
+                state = next;
+                samples.push(state.clone());
+            }
+        }
+    }
+    Some(PatchedConicTrajectory { samples, events })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planet::DEFAULT_DATA_PATH;
+    use crate::units::{Position, Velocity};
+
+    fn system() -> SolarSystem {
+        SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load")
+    }
+
+    /// A probe 7000 km from the Earth's centre going fast enough to escape
+    fn escaping_probe() -> State {
+        State::new(Frame::ecliptic("Earth"), Time::default(), Position::km([7000.0, 0.0, 0.0]), Velocity::km_per_s([0.0, 12.0, 0.0]))
+    }
+
+    #[test]
+    fn earth_spheres_of_influence() {
+        let spheres = system().influence_spheres("Earth").expect("Earth is in the system");
+        // a (m/M)^(2/5) and a (1-e) (m/3M)^(1/3) with the data file's masses
+        assert!((spheres.laplace.in_km() - 9.25E5).abs() < 0.01E5, "Laplace sphere {}", spheres.laplace);
+        assert!((spheres.hill.in_km() - 1.47E6).abs() < 0.01E6, "Hill sphere {}", spheres.hill);
+        assert!(system().influence_spheres("Vulcan").is_none());
+    }
+
+    #[test]
+    fn escaping_probe_switches_to_the_sun_at_the_laplace_sphere() {
+        let system = system();
+        let laplace = system.influence_spheres("Earth").expect("Earth is in the system").laplace;
+        let trajectory = propagate_patched_conic(&system, escaping_probe(), Time::days(10.0), Time::seconds(3600.0))
+            .expect("the probe starts around a body in the system");
+        let [event] = trajectory.events.as_slice() else { panic!("expected one crossing, got {:?}", trajectory.events) };
+        assert_eq!((event.crossing, event.from.as_str()), (SoiCrossing::Exit, "Earth"));
+        assert_eq!(event.state.frame, Frame::ecliptic("Sun"));
+        assert_eq!(event.julian_date, system.epoch + event.state.time);
+
+        // Where the Earth was at the crossing, the probe should be a Laplace radius from it
+        let (earth, _) = system.ephemeris("Earth", event.state.time).expect("Earth is in the system").vectors();
+        let distance = Length::km(norm(sub(event.state.position, earth)));
+        assert!((distance - laplace).abs() < Length::km(1.0), "crossed {} from the Earth, sphere is {}", distance, laplace);
+        let last = trajectory.samples.last().expect("samples are kept");
+        assert_eq!((last.frame.clone(), last.time), (Frame::ecliptic("Sun"), Time::days(10.0)));
+    }
+
+    #[test]
+    fn bad_steps_and_unknown_bodies_give_nothing() {
+        let system = system();
+        assert!(propagate_patched_conic(&system, escaping_probe(), Time::days(1.0), Time::default()).is_none());
+        assert!(propagate_patched_conic(&system, escaping_probe(), Time::days(1.0), Time::seconds(f64::NAN)).is_none());
+        let lost = State { frame: Frame::ecliptic("Vulcan"), ..escaping_probe() };
+        assert!(propagate_patched_conic(&system, lost, Time::days(1.0), Time::seconds(3600.0)).is_none());
+    }
+}
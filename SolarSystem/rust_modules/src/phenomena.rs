@@ -0,0 +1,234 @@
+use std::f64::consts::TAU;
+
+use crate::eclipses::find_events;
+use crate::epoch::DateRange;
+use crate::planet::SolarSystem;
+use crate::units::{Angle, JulianDate, Length, Time};
+use crate::vector::{cross, dot, norm, sub};
+
+/// Times of greatest and least elongation are refined until they're known to this many seconds
+const TIME_TOLERANCE: f64 = 1E-1;
+const MAX_BISECTIONS: usize = 100;
+/// Time offset (s) for the numerical slope of the elongation
+const SLOPE_STEP: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhenomenonKind {
+    InferiorConjunction, // passing between the observer and the Sun
+    SuperiorConjunction, // passing behind the Sun, the only kind of conjunction outer planets have
+    Opposition, // opposite the Sun in the observer's sky
+    GreatestEasternElongation, // furthest from the Sun in the evening sky
+    GreatestWesternElongation, // furthest from the Sun in the morning sky
+}
+
+/// A planet lined up with the Sun as seen from the observer, or as far from it as it gets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phenomenon {
+    pub kind: PhenomenonKind,
+    pub body: String,
+    pub observer: String,
+    pub julian_date: JulianDate,
+    pub elongation: Angle, // between the Sun and the body seen from the observer
+    pub distance: Length, // from the observer
+    pub sun_distance: Length, // from the body to the Sun
+}
+
+/// Several bodies bunched together in direction as seen from a centre
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alignment {
+    pub bodies: Vec<String>,
+    pub center: String,
+    pub start: JulianDate, // when the bodies came within the tolerance
+    pub tightest: JulianDate,
+    pub end: JulianDate, // when they spread out again
+    pub spread: Angle, // the narrowest range of ecliptic longitude holding all the bodies, at the tightest
+    pub longitudes: Vec<Angle>, // ecliptic longitude of each body seen from the centre at the tightest
+}
+
+/// Ecliptic longitude (radians, 0 to 2 pi) of a direction
+fn longitude(direction: [f64; 3]) -> f64 {
+    direction[1].atan2(direction[0]).rem_euclid(TAU)
+}
+
+/// Narrowest range (radians) of longitude that holds every one of `longitudes`, 2 pi less the widest gap between them
+fn spread(longitudes: &[f64]) -> f64 {
+    let mut sorted = longitudes.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let (Some(first), Some(last)) = (sorted.first(), sorted.last()) else { return 0.0 };
+    let widest_gap = sorted.windows(2).map(|pair| pair[1] - pair[0]).fold(first + TAU - last, f64::max);
+    TAU - widest_gap
+}
+
+/// Times (s past the epoch) where `value` has a local maximum (true) or minimum (false) between two of `times`
+fn extrema(times: &[f64], value: impl Fn(f64) -> f64) -> Vec<(f64, bool)> {
+    let slope = |time: f64| value(time + SLOPE_STEP) - value(time - SLOPE_STEP);
+    let mut found = Vec::new();
+    for pair in times.windows(2) {
+        let (before, after) = (slope(pair[0]), slope(pair[1]));
+        let rising = before > 0.0;
+        if rising == (after > 0.0) {
+            continue;
+        }
+        let (mut low, mut high) = (pair[0], pair[1]);
+        for _ in 0..MAX_BISECTIONS {
+            if high - low < TIME_TOLERANCE {
+                break;
+            }
+            let middle = 0.5 * (low + high);
+            if (slope(middle) > 0.0) == rising {
+                low = middle;
+            }
+            else {
+                high = middle;
+            }
+        }
+        found.push((0.5 * (low + high), rising));
+    }
+    found
+}
+
+impl SolarSystem {
+    /// Conjunctions, oppositions and greatest elongations of every planet seen from `observer` (any body) between the
+    /// dates, with the planets following their elements (see `SolarSystem::heliocentric_ephemeris`).
+    /// Conjunctions are taken where the planet comes closest to the Sun in the sky rather than where their ecliptic
+    /// longitudes match, and the step should be well under half the shortest synodic period.
+    /// In time order, None if the observer isn't in the system
+    pub fn phenomena(&self, observer: &str, dates: DateRange) -> Option<Vec<Phenomenon>> {
+        self.find(observer)?;
+        let seconds = |julian_date: JulianDate| (julian_date - self.epoch).in_seconds();
+        let times: Vec<f64> = dates.dates().into_iter().map(seconds).collect();
+        let observer_position =
+            |time: f64| self.heliocentric_ephemeris(observer, Time::seconds(time)).map_or([0.0; 3], |state| state.position);
+        // The planet the observer is on or orbits has no phenomena of its own
+        let mut home = observer.to_string();
+        while let Some(parent) = self.parent_name(&home).filter(|parent| *parent != self.central_body.name) {
+            home = parent;
+        }
+        let mut phenomena = Vec::new();
+        for name in self.bodies.keys().filter(|name| **name != home) {
+            let position = |time: f64| self.heliocentric_ephemeris(name, Time::seconds(time)).map_or([0.0; 3], |state| state.position);
+            let elongation = |time: f64| {
+                let (to_sun, to_body) = (sub([0.0; 3], observer_position(time)), sub(position(time), observer_position(time)));
+                (dot(to_sun, to_body) / (norm(to_sun) * norm(to_body))).clamp(-1.0, 1.0).acos()
+            };
+            for (time, greatest) in extrema(&times, elongation) {
+                let (observer_at, body_at) = (observer_position(time), position(time));
+                let distance = norm(sub(body_at, observer_at));
+                let inner = norm(body_at) < norm(observer_at);
+                let kind = match (greatest, inner) {
+                    (false, _) if distance < norm(observer_at) => PhenomenonKind::InferiorConjunction,
+                    (false, _) => PhenomenonKind::SuperiorConjunction,
+                    (true, false) => PhenomenonKind::Opposition,
+                    // East of the Sun means anticlockwise from it seen from the north ecliptic pole
+                    (true, true) if cross(sub([0.0; 3], observer_at), sub(body_at, observer_at))[2] > 0.0 => {
+                        PhenomenonKind::GreatestEasternElongation
+                    }
+                    (true, true) => PhenomenonKind::GreatestWesternElongation,
+                };
+                phenomena.push(Phenomenon {
+                    kind,
+                    body: name.clone(),
+                    observer: observer.to_string(),
+                    julian_date: self.epoch + Time::seconds(time),
+                    elongation: Angle::radians(elongation(time)),
+                    distance: Length::km(distance),
+                    sun_distance: Length::km(norm(body_at)),
+                });
+            }
+        }
+        phenomena.sort_by(|a, b| a.julian_date.total_cmp(&b.julian_date));
+        Some(phenomena)
+    }
+
+    /// Times between the dates when all of `bodies` lie within `tolerance` of ecliptic longitude of each
+    /// other as seen from `center` (any body, or the central body for heliocentric alignments), with the bodies
+    /// following their elements. Latitude is ignored, as in the usual sense of planets lining up.
+    /// In time order, None if any of the bodies or the centre isn't in the system
+    pub fn alignments(&self, bodies: &[&str], center: &str, tolerance: Angle, dates: DateRange) -> Option<Vec<Alignment>> {
+        for name in bodies.iter().chain([&center]) {
+            self.heliocentric_ephemeris(name, Time::default())?;
+        }
+        let times: Vec<f64> = dates.dates().into_iter().map(|julian_date| (julian_date - self.epoch).in_seconds()).collect();
+        let longitudes = |time: f64| -> Vec<f64> {
+            let center_position = self.heliocentric_ephemeris(center, Time::seconds(time)).map_or([0.0; 3], |state| state.position);
+            bodies
+                .iter()
+                .map(|name| {
+                    let position = self.heliocentric_ephemeris(name, Time::seconds(time)).map_or([0.0; 3], |state| state.position);
+                    longitude(sub(position, center_position))
+                })
+                .collect()
+        };
+        let alignments = find_events(&times, |time| spread(&longitudes(time)) - tolerance.in_radians())
+            .into_iter()
+            .map(|(start, tightest, end)| {
+                let at_tightest = longitudes(tightest);
+                Alignment {
+                    bodies: bodies.iter().map(|name| name.to_string()).collect(),
+                    center: center.to_string(),
+                    start: self.epoch + Time::seconds(start),
+                    tightest: self.epoch + Time::seconds(tightest),
+                    end: self.epoch + Time::seconds(end),
+                    spread: Angle::radians(spread(&at_tightest)),
+                    longitudes: at_tightest.into_iter().map(Angle::radians).collect(),
+                }
+            })
+            .collect();
+        Some(alignments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch::julian_date;
+    use crate::planet::DEFAULT_DATA_PATH;
+
+    /// Phenomena seen from the Earth from June 2003 to September 2004, a day apart
+    fn from_earth() -> Vec<Phenomenon> {
+        let system = SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load");
+        let dates = DateRange { start: julian_date(2003, 6, 1.0), end: julian_date(2004, 9, 1.0), step: Time::days(1.0) };
+        system.phenomena("Earth", dates).expect("the Earth is in the system")
+    }
+
+    fn only(phenomena: &[Phenomenon], kind: PhenomenonKind, body: &str) -> Phenomenon {
+        let found: Vec<&Phenomenon> = phenomena.iter().filter(|phenomenon| phenomenon.kind == kind && phenomenon.body == body).collect();
+        let [phenomenon] = found.as_slice() else { panic!("expected one {:?} of {}, got {:?}", kind, body, found) };
+        (*phenomenon).clone()
+    }
+
+    /// Within half a day of noon on the given date
+    fn near(found: JulianDate, (year, month, day): (i32, u32, f64)) -> bool {
+        (found - julian_date(year, month, day + 0.5)).abs() < Time::days(0.5)
+    }
+
+    #[test]
+    fn mars_opposition_of_2003() {
+        let opposition = only(&from_earth(), PhenomenonKind::Opposition, "Mars");
+        assert!(near(opposition.julian_date, (2003, 8, 28.0)), "{}", opposition.julian_date);
+        // Mars was about 56 million km away, its closest in 60000 years
+        assert!((opposition.distance.in_km() - 5.58E7).abs() < 0.02E7, "{}", opposition.distance);
+        assert!(opposition.elongation > Angle::degrees(170.0));
+    }
+
+    #[test]
+    fn venus_elongation_and_conjunction_of_2004() {
+        let phenomena = from_earth();
+        let elongation = only(&phenomena, PhenomenonKind::GreatestEasternElongation, "Venus");
+        assert!(near(elongation.julian_date, (2004, 3, 29.0)), "{}", elongation.julian_date);
+        assert!((elongation.elongation.in_degrees() - 46.0).abs() < 0.1, "{} degrees", elongation.elongation.in_degrees());
+        // The conjunction of the 2004 transit
+        let conjunction = only(&phenomena, PhenomenonKind::InferiorConjunction, "Venus");
+        assert!(near(conjunction.julian_date, (2004, 6, 8.0)), "{}", conjunction.julian_date);
+        assert!(conjunction.elongation < Angle::degrees(0.5));
+        assert!(phenomena.iter().all(|phenomenon| phenomenon.body != "Earth"), "the observer's own planet has no phenomena");
+        assert!(phenomena.windows(2).all(|pair| pair[0].julian_date <= pair[1].julian_date));
+    }
+
+    #[test]
+    fn unknown_observer_gives_nothing() {
+        let system = SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load");
+        let dates = DateRange { start: julian_date(2003, 6, 1.0), end: julian_date(2003, 7, 1.0), step: Time::days(1.0) };
+        assert!(system.phenomena("Vulcan", dates).is_none());
+    }
+}
@@ -1,214 +1,845 @@
-use std::{fs::File, collections::HashMap, io::Read, vec, f64::consts::TAU};
+use std::{f64::consts::{PI, TAU}, fs, collections::HashMap, path::Path, str::FromStr, vec};
 use toml::{self, Table, de::Error as TomlError};
-use serde::Deserialize;
 
-/// Takes an angle in radians and wraps it between 0 and TAUT (2*pi)
-fn wrap_angle(angle: f64) -> f64 {
-    let wrapped_angle = angle % TAU;
-    if wrapped_angle < 0.0 {
-        wrapped_angle + TAU
-    }
-    else {
-        wrapped_angle
+use crate::kepler::{eccentric_anomaly, mean_anomaly, true_anomaly, true_anomaly_from_eccentric, wrap_angle};
+use crate::error::{LoadError, Location};
+use crate::epoch::DAYS_PER_JULIAN_CENTURY;
+use crate::frames::{Frame, Rotation, State};
+use crate::harmonics::ZonalHarmonics;
+use crate::non_gravitational::{Atmosphere, SpacecraftProperties};
+use crate::orbit_propagration::{get_mu, MASS_OF_SUN, RADIUS_OF_SUN};
+use crate::schema::{BodyRecord, SystemRecord};
+use crate::units::{Angle, AngularRate, GravitationalParameter, JulianDate, Length, Mass, SpecificAngularMomentum, SpecificEnergy, Speed, Time};
+use crate::vector::{add, cross, dot, norm, scale, sub};
+
+/// Name the Sun goes by when asking for states, it sits at the origin of the heliocentric frame
+pub const SUN: &str = "Sun";
+
+/// Whatever the planets orbit, the Sun unless the data file says otherwise
+#[derive(Debug, Clone)]
+pub struct CentralBody {
+    pub name: String,
+    pub mass: Mass,
+    pub radius: Length,
+    pub zonal_harmonics: Option<ZonalHarmonics>, // felt by the planets, a point mass when None
+}
+
+impl Default for CentralBody {
+    fn default() -> Self {
+        Self {
+            name: SUN.to_string(),
+            mass: MASS_OF_SUN,
+            radius: RADIUS_OF_SUN,
+            zonal_harmonics: None,
+        }
     }
 }
 
+/// Eccentricities and inclinations (radians) below this are treated as circular and equatorial
+const DEGENERATE_TOLERANCE: f64 = 1e-11;
+const HALF_TURN: Angle = Angle::radians(PI);
+
+/// True for inclinations outside 0 to 180 degrees, which `OrbitalElements::normalise_inclination` turns round
+fn is_turned_inclination(inclination: Angle) -> bool {
+    inclination.wrapped() > HALF_TURN
+}
+
 /// base elements required to from an orbit
-#[derive(Deserialize, Debug)]
-struct OrbitalElements {
-    semimajor_axis: f64, // km
-    eccentricity: f64, // none
-    inclination: f64,  // radians
-    mean_anomoly: f64, // radians
-    argument_of_parigee: f64, // radians
-    mass: f64, // kg 
-    mu: Option<f64>, // km^3/s^2
-    h: Option<f64> // km^2/s
+#[derive(Debug, Clone)]
+pub struct OrbitalElements {
+    pub semimajor_axis: Length, // negative for hyperbolic orbits
+    pub eccentricity: f64, // none
+    pub inclination: Angle,
+    pub longitude_of_ascending_node: Angle,
+    pub argument_of_parigee: Angle,
+    pub mean_anomoly: Angle,
+    pub mass: Mass,
+    pub mu: Option<GravitationalParameter>, // of the central body and this one together, None until the central mass is known
+    pub h: Option<SpecificAngularMomentum> // known along with mu
 }
 
 impl OrbitalElements {
-    fn new(data: [f64; 6]) -> Self{
-        Self{
-            semimajor_axis: data[0],
+    /// data: semimajor axis (km), eccentricity, inclination, longitude of ascending node,
+    ///       argument of perigee, mean anomaly (all angles in degrees), mass (kg)
+    ///       Inclinations outside 0 to 180 degrees are normalised, see `normalise_inclination`
+    pub(crate) fn new(data: [f64; 7]) -> Self{
+        let mut elements = Self{
+            semimajor_axis: Length::km(data[0]),
             eccentricity: data[1],
-            inclination: wrap_angle(data[2].to_radians()),
-            mean_anomoly: wrap_angle(data[3].to_radians() % (2.0 * std::f64::consts::PI)),
-            argument_of_parigee: wrap_angle(data[4].to_radians() % (2.0 * std::f64::consts::PI)),
-            mass: data[5],
+            inclination: Angle::degrees(data[2]),
+            longitude_of_ascending_node: Angle::degrees(data[3]).wrapped(),
+            argument_of_parigee: Angle::degrees(data[4]).wrapped(),
+            mean_anomoly: Angle::degrees(data[5]).wrapped(),
+            mass: Mass::kg(data[6]),
             mu: None,
             h: None
+        };
+        elements.normalise_inclination();
+        elements
+    }
+
+    /// Brings the inclination between 0 and 180 degrees. A negative inclination is the same orbit as the positive one
+    /// with the node and perigee half a turn round, which is how `from_state_vectors` gives it back
+    fn normalise_inclination(&mut self) {
+        if is_turned_inclination(self.inclination) {
+            self.inclination = Angle::radians(TAU) - self.inclination.wrapped();
+            self.longitude_of_ascending_node = (self.longitude_of_ascending_node + HALF_TURN).wrapped();
+            self.argument_of_parigee = (self.argument_of_parigee + HALF_TURN).wrapped();
+        }
+        else {
+            self.inclination = self.inclination.wrapped();
+        }
+    }
+
+    /// Eccentric anomaly derived from the mean anomaly,
+    /// hyperbolic and parabolic orbits give the anomalies described in `kepler::eccentric_anomaly`
+    pub fn eccentric_anomaly(&self) -> Angle {
+        Angle::radians(eccentric_anomaly(self.mean_anomoly.in_radians(), self.eccentricity))
+    }
+
+    /// True anomaly derived from the mean anomaly
+    pub fn true_anomaly(&self) -> Angle {
+        Angle::radians(true_anomaly_from_eccentric(self.eccentric_anomaly().in_radians(), self.eccentricity))
+    }
+
+    /// State relative to the central body `time` past the system epoch, in `frame`, which should be the ecliptic frame
+    /// of the central body (or whatever frame the elements are given in).
+    /// mu is the gravitational parameter of the central body and this one together
+    pub fn to_state(&self, mu: GravitationalParameter, frame: Frame, time: Time) -> State {
+        let (position, velocity) = self.to_state_vectors(mu);
+        State { frame, time, position, velocity }
+    }
+
+    /// Cartesian position (km) and velocity (km/s) relative to the central body, see `to_state`
+    pub(crate) fn to_state_vectors(&self, mu: GravitationalParameter) -> ([f64; 3], [f64; 3]) {
+        let e = self.eccentricity;
+        let true_anomaly = true_anomaly(self.mean_anomoly.in_radians(), e);
+        // Semi-latus rectum, hyperbolic orbits carry a negative semimajor axis so this stays positive
+        let p = self.semimajor_axis.in_km() * (1.0 - e * e);
+        let r = p / (1.0 + e * true_anomaly.cos());
+        let speed_factor = (mu.in_km3_per_s2() / p).sqrt();
+        // Perifocal frame, x axis points at periapsis
+        let position = [r * true_anomaly.cos(), r * true_anomaly.sin(), 0.0];
+        let velocity = [-speed_factor * true_anomaly.sin(), speed_factor * (e + true_anomaly.cos()), 0.0];
+        (
+            perifocal_to_inertial(position, self.longitude_of_ascending_node, self.inclination, self.argument_of_parigee),
+            perifocal_to_inertial(velocity, self.longitude_of_ascending_node, self.inclination, self.argument_of_parigee),
+        )
+    }
+
+    /// Classical elements from a state relative to the central body, in a frame centred on it.
+    /// mu is the gravitational parameter of the pair and mass belongs to the orbiting body.
+    /// The elements come out in the axes of the state's frame, see `from_state_vectors` for the degenerate cases
+    pub fn from_state(state: &State, gravitational_parameter: GravitationalParameter, mass: Mass) -> Self {
+        Self::from_state_vectors(state.position, state.velocity, gravitational_parameter, mass)
+    }
+
+    /// Classical elements from a position (km) and velocity (km/s) relative to the central body.
+    /// mu is the gravitational parameter of the pair and mass belongs to the orbiting body.
+    /// Angles that are undefined for degenerate orbits follow the usual conventions:
+    ///     equatorial orbits put the ascending node at 0, so the argument of perigee becomes the longitude of perigee
+    ///     circular orbits put perigee at the ascending node, so the mean anomaly becomes the argument of latitude
+    ///     circular equatorial orbits do both, leaving the true longitude in the mean anomaly
+    /// Exactly parabolic states have no finite semimajor axis and come back with an infinite one
+    pub(crate) fn from_state_vectors(position: [f64; 3], velocity: [f64; 3], gravitational_parameter: GravitationalParameter, mass: Mass) -> Self {
+        let mu = gravitational_parameter.in_km3_per_s2();
+        let r = norm(position);
+        let v = norm(velocity);
+        let angular_momentum = cross(position, velocity);
+        let h = norm(angular_momentum);
+        // Points at the ascending node, zero for equatorial orbits
+        let node = [-angular_momentum[1], angular_momentum[0], 0.0];
+        let n = norm(node);
+        let eccentricity_vector = scale(
+            sub(scale(position, v * v - mu / r), scale(velocity, dot(position, velocity))),
+            1.0 / mu,
+        );
+        let e = norm(eccentricity_vector);
+        let energy = 0.5 * v * v - mu / r;
+        let semimajor_axis = -mu / (2.0 * energy);
+        let inclination = (angular_momentum[2] / h).clamp(-1.0, 1.0).acos();
+
+        let circular = e < DEGENERATE_TOLERANCE;
+        let equatorial = n / h < DEGENERATE_TOLERANCE;
+        let longitude_of_ascending_node = if equatorial { 0.0 } else { wrap_angle(node[1].atan2(node[0])) };
+        // Angle from `from` to `to` measured in the orbit plane, in the direction of motion
+        let angle_in_plane = |from: [f64; 3], to: [f64; 3]| {
+            let sine = dot(cross(from, to), angular_momentum) / h;
+            wrap_angle(sine.atan2(dot(from, to)))
+        };
+        // Equatorial orbits measure from the x axis instead of the (undefined) node
+        let reference = if equatorial { [1.0, 0.0, 0.0] } else { node };
+
+        let (argument_of_parigee, true_anomaly) = if circular {
+            (0.0, angle_in_plane(reference, position))
+        }
+        else {
+            (angle_in_plane(reference, eccentricity_vector), angle_in_plane(eccentricity_vector, position))
+        };
+        let eccentricity = if circular { 0.0 } else { e };
+
+        Self {
+            semimajor_axis: Length::km(semimajor_axis),
+            eccentricity,
+            inclination: Angle::radians(inclination),
+            longitude_of_ascending_node: Angle::radians(longitude_of_ascending_node),
+            argument_of_parigee: Angle::radians(argument_of_parigee),
+            mean_anomoly: Angle::radians(mean_anomaly(true_anomaly, eccentricity)),
+            mass,
+            mu: Some(gravitational_parameter),
+            h: Some(SpecificAngularMomentum::km2_per_s(h)),
+        }
+    }
+}
+
+/// How quickly the orbital elements drift, per Julian century, as given in JPL's approximate positions table
+#[derive(Debug, Clone)]
+pub struct ElementRates {
+    pub semimajor_axis: Length,
+    pub eccentricity: f64, // none
+    pub inclination: Angle,
+    pub longitude_of_ascending_node: Angle,
+    pub argument_of_parigee: Angle,
+    pub mean_anomoly: Angle, // includes the mean motion
+}
+
+impl ElementRates {
+    /// Same layout as the body data: semi major axis (km), eccentricity, inclination, mean longitude,
+    /// longitude of perigee, longitude of ascending node, all per century and angles in degrees
+    fn new(data: [f64; 6]) -> Self {
+        Self {
+            semimajor_axis: Length::km(data[0]),
+            eccentricity: data[1],
+            inclination: Angle::degrees(data[2]),
+            longitude_of_ascending_node: Angle::degrees(data[5]),
+            argument_of_parigee: Angle::degrees(data[4] - data[5]),
+            mean_anomoly: Angle::degrees(data[3] - data[4]),
+        }
+    }
+
+    /// The same rates for elements whose inclination has been turned round by `normalise_inclination`,
+    /// the inclination then changes the other way
+    fn turned(&self) -> Self {
+        Self {
+            inclination: -self.inclination,
+            ..self.clone()
+        }
+    }
+
+    /// These rates as seen from elements moved on by `centuries`, which turn round if their inclination passes 0 or 180 degrees
+    fn after(&self, elements: &OrbitalElements, centuries: f64) -> Self {
+        if is_turned_inclination(elements.inclination + self.inclination * centuries) {
+            self.turned()
+        }
+        else {
+            self.clone()
         }
     }
 }
 
+impl OrbitalElements {
+    /// These elements moved on by `centuries` Julian centuries at the given rates
+    pub fn with_rates(&self, rates: &ElementRates, centuries: f64) -> Self {
+        let mut moved = Self {
+            semimajor_axis: self.semimajor_axis + rates.semimajor_axis * centuries,
+            eccentricity: self.eccentricity + rates.eccentricity * centuries,
+            inclination: self.inclination + rates.inclination * centuries,
+            longitude_of_ascending_node: (self.longitude_of_ascending_node + rates.longitude_of_ascending_node * centuries).wrapped(),
+            argument_of_parigee: (self.argument_of_parigee + rates.argument_of_parigee * centuries).wrapped(),
+            mean_anomoly: (self.mean_anomoly + rates.mean_anomoly * centuries).wrapped(),
+            mass: self.mass,
+            mu: None,
+            h: None,
+        };
+        moved.normalise_inclination();
+        match self.mu {
+            Some(mu) => moved.with_gravitational_parameter(mu),
+            None => moved,
+        }
+    }
+
+    /// These elements with `mu` (the central body's and this one's together) filled in, along with the specific
+    /// angular momentum it gives
+    pub fn with_gravitational_parameter(&self, mu: GravitationalParameter) -> Self {
+        let semi_latus_rectum = self.semimajor_axis.in_km() * (1.0 - self.eccentricity * self.eccentricity);
+        let h = SpecificAngularMomentum::km2_per_s((mu.in_km3_per_s2() * semi_latus_rectum).sqrt());
+        Self { mu: Some(mu), h: Some(h), ..self.clone() }
+    }
+
+    /// Specific orbital energy (km^2/s^2), -mu/2a: negative for bound orbits and positive for hyperbolic ones.
+    /// None if mu isn't known
+    pub fn specific_energy(&self) -> Option<SpecificEnergy> {
+        Some(SpecificEnergy::km2_per_s2(-self.mu?.in_km3_per_s2() / (2.0 * self.semimajor_axis.in_km())))
+    }
+
+    /// Vector from the central body towards periapsis with the size of the eccentricity, in the system's frame
+    pub fn eccentricity_vector(&self) -> [f64; 3] {
+        scale(
+            perifocal_to_inertial([1.0, 0.0, 0.0], self.longitude_of_ascending_node, self.inclination, self.argument_of_parigee),
+            self.eccentricity,
+        )
+    }
+
+    /// These elements moved on by `time` along a fixed Keplerian orbit, only the mean anomaly changes
+    pub fn after(&self, mu: GravitationalParameter, time: Time) -> Self {
+        let mean_anomoly = self.mean_anomoly + self.mean_motion(mu) * time;
+        Self {
+            mean_anomoly: if self.eccentricity < 1.0 { mean_anomoly.wrapped() } else { mean_anomoly },
+            ..self.clone()
+        }
+    }
+
+    /// Mean motion, the rate the mean anomaly advances. Hyperbolic orbits use the size of the semimajor axis
+    pub fn mean_motion(&self, mu: GravitationalParameter) -> AngularRate {
+        AngularRate::radians_per_s((mu.in_km3_per_s2() / self.semimajor_axis.in_km().abs().powi(3)).sqrt())
+    }
+
+    /// Time for one revolution, None for orbits that never come back
+    pub fn period(&self, mu: GravitationalParameter) -> Option<Time> {
+        (self.eccentricity < 1.0).then(|| Time::seconds(TAU / self.mean_motion(mu).in_radians_per_s()))
+    }
+
+    /// Semi-latus rectum, the distance from the central body at 90 degrees from periapsis
+    pub fn semi_latus_rectum(&self) -> Length {
+        self.semimajor_axis * (1.0 - self.eccentricity * self.eccentricity)
+    }
+
+    /// Closest distance to the central body
+    pub fn periapsis_radius(&self) -> Length {
+        self.semimajor_axis * (1.0 - self.eccentricity)
+    }
+
+    /// Furthest distance from the central body, None for orbits that never come back
+    pub fn apoapsis_radius(&self) -> Option<Length> {
+        (self.eccentricity < 1.0).then(|| self.semimajor_axis * (1.0 + self.eccentricity))
+    }
+
+    /// Distance from the central body at a true anomaly
+    pub fn radius_at(&self, true_anomaly: Angle) -> Length {
+        self.semi_latus_rectum() / (1.0 + self.eccentricity * true_anomaly.cos())
+    }
+
+    /// Speed at a distance from the central body from the vis-viva equation.
+    /// NaN beyond the apoapsis, where the orbit can't reach
+    pub fn speed_at(&self, mu: GravitationalParameter, radius: Length) -> Speed {
+        Speed::km_per_s((mu.in_km3_per_s2() * (2.0 / radius.in_km() - 1.0 / self.semimajor_axis.in_km())).sqrt())
+    }
+
+    /// Speed at periapsis, the fastest point of the orbit
+    pub fn periapsis_speed(&self, mu: GravitationalParameter) -> Speed {
+        self.speed_at(mu, self.periapsis_radius())
+    }
+
+    /// Speed at apoapsis, None for orbits that never come back
+    pub fn apoapsis_speed(&self, mu: GravitationalParameter) -> Option<Speed> {
+        self.apoapsis_radius().map(|radius| self.speed_at(mu, radius))
+    }
+
+    /// Angle of the velocity above the local horizontal at a true anomaly, positive while climbing away from periapsis
+    pub fn flight_path_angle(&self, true_anomaly: Angle) -> Angle {
+        let (sine, cosine) = true_anomaly.sin_cos();
+        Angle::radians((self.eccentricity * sine).atan2(1.0 + self.eccentricity * cosine))
+    }
+}
+
+/// Rotates a perifocal vector into the reference frame through the 3-1-3 sequence (node, inclination, perigee)
+fn perifocal_to_inertial(vector: [f64; 3], ascending_node: Angle, inclination: Angle, arg_of_perigee: Angle) -> [f64; 3] {
+    let (sin_node, cos_node) = ascending_node.sin_cos();
+    let (sin_inc, cos_inc) = inclination.sin_cos();
+    let (sin_arg, cos_arg) = arg_of_perigee.sin_cos();
+    let [x, y, _] = vector;
+    [
+        (cos_node * cos_arg - sin_node * sin_arg * cos_inc) * x + (-cos_node * sin_arg - sin_node * cos_arg * cos_inc) * y,
+        (sin_node * cos_arg + cos_node * sin_arg * cos_inc) * x + (-sin_node * sin_arg + cos_node * cos_arg * cos_inc) * y,
+        (sin_arg * sin_inc) * x + (cos_arg * sin_inc) * y,
+    ]
+}
+
 
 /// In my simulation, there are two types of bodies
-#[derive(Deserialize, Debug)]
-enum BodyType {
+#[derive(Debug, Clone)]
+pub enum BodyType {
     Planet,
     Satellite
 }
 
 /// Data for a body, includes a reference to requisite orbital data
-#[derive(Deserialize, Debug)]
-struct Body {
-    coords: Vec<[f64; 3]>,
-    vel: Vec<[f64; 3]>,
-    radius: i32,
-    orbit_data: OrbitalElements,
-    moons: Option<HashMap<String, Body>>,
-    importance: BodyType,
+#[derive(Debug, Clone)]
+pub struct Body {
+    pub(crate) coords: Vec<[f64; 3]>, // km, relative to what the body orbits, see `SolarSystem::states`
+    pub(crate) vel: Vec<[f64; 3]>, // km/s
+    pub(crate) times: Vec<Time>, // past the system epoch, matching each entry of coords and vel
+    pub radius: Length, // mean radius
+    pub orbit_data: OrbitalElements, // at the system epoch
+    pub rates: Option<ElementRates>, // bodies without rates follow a fixed Keplerian orbit
+    pub zonal_harmonics: Option<ZonalHarmonics>, // of this body's own field, felt by its moons
+    pub rotation: Option<Rotation>, // for frames fixed to the body
+    pub atmosphere: Option<Atmosphere>, // drag on its moons that have spacecraft properties
+    pub spacecraft: Option<SpacecraftProperties>, // radiation pressure and drag act on bodies with these
+    pub moons: Option<HashMap<String, Body>>,
+    pub importance: BodyType,
 }
 
 impl Body{
-    /// The creation of a now body.
-    /// data: semi major axis [0]
-    ///       eccentricity [1]
-    ///       inclination [2] 
-    ///       mean_longitude [3]
-    ///       longitude of perigee [4]
-    ///       longitude of ascending node [5]
-    ///       radius of body [6]
-    ///       mass of body [7]
-    fn new(data: Vec<f64>) -> Self{
-        let arg_of_perigee = data[4] - data[5];
-        let mean_anomaly = data[3] - data[4];
+    /// The creation of a new body, and its moons/satellites, from its record in the data file.
+    /// Moons are always satellites whatever `importance` their parent has
+    fn from_record(record: &BodyRecord, importance: BodyType) -> Self{
+        let arg_of_perigee = record.longitude_of_perihelion_degrees - record.longitude_of_the_ascending_node_degrees;
+        let mean_anomaly = record.mean_longitude_degrees - record.longitude_of_perihelion_degrees;
+        let moons = (!record.moons.is_empty()).then(|| {
+            record.moons.iter().map(|(name, moon)| (name.clone(), Body::from_record(moon, BodyType::Satellite))).collect()
+        });
+        // The rates are for the inclination as given, which the elements may have turned round
+        let turned = is_turned_inclination(Angle::degrees(record.inclination_degrees));
         Self {
-            coords: vec![[data[0], 0.0, 0.0]],
+            coords: vec![[record.semi_major_axis_km, 0.0, 0.0]],
             vel: vec![[0.0; 3]],
-            radius: data[7] as i32,
-            moons: None,
-            orbit_data: OrbitalElements::new([data[0], 
-                data[1], 
-                data[2], 
-                mean_anomaly, 
+            times: vec![Time::default()],
+            radius: Length::km(record.meanradius_km),
+            moons,
+            rates: record.rates_per_century.as_ref().map(|rates| ElementRates::new([
+                rates.semi_major_axis_km,
+                rates.eccentricity,
+                rates.inclination_degrees,
+                rates.mean_longitude_degrees,
+                rates.longitude_of_perihelion_degrees,
+                rates.longitude_of_the_ascending_node_degrees,
+            ])).map(|rates| if turned { rates.turned() } else { rates }),
+            zonal_harmonics: record.zonal_harmonics.as_ref().map(ZonalHarmonics::from_record),
+            rotation: record.rotation.as_ref().map(Rotation::from_record),
+            atmosphere: record.atmosphere.as_ref().map(Atmosphere::from_record),
+            spacecraft: record.spacecraft.as_ref().map(SpacecraftProperties::from_record),
+            orbit_data: OrbitalElements::new([record.semi_major_axis_km, 
+                record.eccentricity, 
+                record.inclination_degrees, 
+                record.longitude_of_the_ascending_node_degrees, 
                 arg_of_perigee, 
-                data[7]]),
-            importance: BodyType::Planet
+                mean_anomaly, 
+                record.mass_kg]),
+            importance
         }
     }
 
-    /// Same as but withwith moons/satellites
-    fn with_moons(data: Vec<f64>, moons_: HashMap<String, Body>) -> Self{
-        let arg_of_perigee = data[4] - data[5];
-        let mean_anomaly = data[3] - data[4];
-        Self {
-            coords: vec![[data[0], 0.0, 0.0]],
-            vel: vec![[0.0; 3]],
-            radius: data[7] as i32,
-            moons: Some(moons_),
-            orbit_data: OrbitalElements::new([data[0], 
-                data[1], 
-                data[2], 
-                mean_anomaly, 
-                arg_of_perigee, 
-                data[7]]),
-            importance: BodyType::Planet
+    /// Replaces the starting position and velocity with the ones given by the orbital elements,
+    /// central_mass is the mass of whatever this body orbits. Moons are set up around this body
+    pub fn initialize_state(&mut self, central_mass: Mass) {
+        let mu = get_mu(central_mass, self);
+        self.orbit_data = self.orbit_data.with_gravitational_parameter(mu);
+        let (position, velocity) = self.orbit_data.to_state_vectors(mu);
+        self.coords = vec![position];
+        self.vel = vec![velocity];
+        self.times = vec![Time::default()];
+        let mass = self.orbit_data.mass;
+        if let Some(moons) = self.moons.as_mut() {
+            for moon in moons.values_mut() {
+                moon.initialize_state(mass);
+            }
         }
     }
 
-    /// Same as normal new function with exception that it is used for satellites/moons
-    fn new_satellite(data: Vec<f64>) -> Self {
-        let arg_of_perigee = data[4] - data[5];
-        let mean_anomaly = data[3] - data[4];
-        Self {
-            coords: vec![[data[0], 0.0, 0.0]],
-            vel: vec![[0.0; 3]],
-            radius: data[7] as i32,
-            moons: None,
-            orbit_data: OrbitalElements::new([data[0], 
-                data[1], 
-                data[2], 
-                mean_anomaly, 
-                arg_of_perigee, 
-                data[7]]),
-            importance: BodyType::Satellite
+    /// Elements `time` past the epoch they were given for, central_mass and central_harmonics belonging to
+    /// whatever this body orbits. Element rates are used when the body has them, otherwise the orbit is Keplerian
+    /// with the J2 secular drift of what it orbits if that has zonal harmonics
+    pub fn elements_at(&self, central_mass: Mass, central_harmonics: Option<&ZonalHarmonics>, time: Time) -> OrbitalElements {
+        match (self.rates.as_ref(), central_harmonics) {
+            (Some(rates), _) => self.orbit_data.with_rates(rates, time.in_days() / DAYS_PER_JULIAN_CENTURY),
+            (None, Some(harmonics)) => harmonics.advance(&self.orbit_data, get_mu(central_mass, self), time),
+            (None, None) => self.orbit_data.after(get_mu(central_mass, self), time),
         }
     }
+
+    /// Copy of this body with its elements and starting state at `centuries` Julian centuries past
+    /// the epoch they were given for, see `elements_at`. History is dropped
+    fn at(&self, central_mass: Mass, central_harmonics: Option<&ZonalHarmonics>, centuries: f64) -> Self {
+        let orbit_data = self.elements_at(central_mass, central_harmonics, Time::days(centuries * DAYS_PER_JULIAN_CENTURY));
+        let moons = self.moons.as_ref().map(|moons| {
+            moons
+                .iter()
+                .map(|(name, moon)| (name.clone(), moon.at(self.orbit_data.mass, self.zonal_harmonics.as_ref(), centuries)))
+                .collect()
+        });
+        let mut body = Self {
+            orbit_data,
+            rates: self.rates.as_ref().map(|rates| rates.after(&self.orbit_data, centuries)),
+            moons,
+            ..self.clone()
+        };
+        body.initialize_state(central_mass);
+        body
+    }
 }
 
 /// Struct holding the hashmap of all bodies, 
-/// it is a struct because I may add more elements in the future (such as epoch)
-#[derive(Deserialize)]
+/// along with the epoch their elements are given at
+#[derive(Clone)]
 pub struct SolarSystem{
-    bodies: HashMap<String, Body>
+    pub bodies: HashMap<String, Body>,
+    pub central_body: CentralBody, // sits at the origin, every body's state is relative to it
+    pub epoch: JulianDate, // body times are seconds past this
+    pub valid_range: Option<(JulianDate, JulianDate)> // the element rates can be trusted between these dates
 }
 
 impl SolarSystem{
-    fn new(bodies_in_system: HashMap<String, Body>) -> Self{
+    fn new(bodies_in_system: HashMap<String, Body>, central_body: CentralBody, epoch: JulianDate, valid_range: Option<(JulianDate, JulianDate)>) -> Self{
         Self{
-            bodies: bodies_in_system
+            bodies: bodies_in_system,
+            central_body,
+            epoch,
+            valid_range
         }
     }
-}
 
-/// More comments throughout but reades the toml of data for bodies in the system, packs the structs,
-/// and returns a full instance of SolarSystem
-pub fn setup_from_toml() -> SolarSystem{
-    // Read the TOML file
-    let mut file = File::open("../data/celestial_bodies_data.toml").expect("Failed to open file");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).expect("Failed to read file");
-
-    // Parse the TOML into a struct
-    let config: Result<Table, TomlError> = toml::from_str(&contents);
-    let mut number_of_bodies = 0;
-    let mut system = HashMap::new();
-    match config {
-        Ok(config) => {
-            // Set number of bodies
-            number_of_bodies = config.get("number_of_bodies").and_then(toml::Value::as_integer).expect("Could not find the number of bodies").try_into().unwrap();
-            // Iterate through bodies in the solar system, as specified in data.toml
-            for (name, body) in config.get("SolarSystem").and_then(|s| s.as_table()).expect("SolarSystem not found in TOML").iter(){
-                if let Some(moons) = body.get("moons").and_then(toml::Value::as_table){
-                    let mut map_o_moons = HashMap::new();
-                    // Creating moons
-                    // could be satellites
-                    for (moon_name, moon) in moons{
-                        map_o_moons.insert(moon_name.clone(), 
-                        Body::new_satellite(vec![moon.get("semi_major_axis_km").and_then(toml::Value::as_float).expect("Couldn't find semimajor axis of a moon"), 
-                        moon.get("eccentricity").and_then(toml::Value::as_float).expect("Couldn't find eccentricity of a moon") , 
-                        moon.get("inclination_degrees").and_then(toml::Value::as_float).expect("Couldn't find inclination of a moon") ,
-                        moon.get("mean_longitude_degrees").and_then(toml::Value::as_float).expect("Couldn't find mean longitude of a moon") ,
-                        moon.get("longitude_of_perhelion_degrees").and_then(toml::Value::as_float).expect("Couldn't find longitude of perigee of a moon") ,
-                        moon.get("longitude_of_the_ascending_node_degrees").and_then(toml::Value::as_float).expect("Couldn't find ascending node of a moon") ,
-                        moon.get("meanradius_km").and_then(toml::Value::as_float).expect("Couldn't find radius of a moon") ,
-                        moon.get("mass_kg").and_then(toml::Value::as_float).expect("Couldn't find mass of a moon") 
-                    ]));
+    /// The whole system at another Julian date: bodies with element rates have them applied,
+    /// the rest are moved along their Keplerian orbits. Each body starts a fresh history at that date.
+    /// None if the date is outside the range the rates are valid for
+    pub fn at(&self, date: JulianDate) -> Option<SolarSystem> {
+        if let Some((from, to)) = self.valid_range {
+            if date < from || date > to {
+                return None;
+            }
+        }
+        let centuries = (date - self.epoch).in_days() / DAYS_PER_JULIAN_CENTURY;
+        let central = &self.central_body;
+        let bodies = self
+            .bodies
+            .iter()
+            .map(|(name, body)| (name.clone(), body.at(central.mass, central.zonal_harmonics.as_ref(), centuries)))
+            .collect();
+        Some(SolarSystem::new(bodies, self.central_body.clone(), date, self.valid_range))
+    }
+
+    /// Chain of bodies from a planet down to the named body, e.g. [Earth, Moon, some satellite].
+    /// None if no body at any depth has that name
+    pub fn lineage(&self, name: &str) -> Option<Vec<&Body>> {
+        fn search<'a>(bodies: &'a HashMap<String, Body>, name: &str, chain: &mut Vec<&'a Body>) -> bool {
+            for (body_name, body) in bodies {
+                chain.push(body);
+                if body_name == name {
+                    return true;
+                }
+                if let Some(moons) = body.moons.as_ref() {
+                    if search(moons, name, chain) {
+                        return true;
                     }
-                    // Moons/satellites are created, create the body
-                    system.insert(name.clone(), 
-                        Body::with_moons(vec![body.get("semi_major_axis_km").and_then(toml::Value::as_float).expect("Couldn't find semimajor axis of a planet"), 
-                        body.get("eccentricity").and_then(toml::Value::as_float).expect("Couldn't find eccentricity of a planet") , 
-                        body.get("inclination_degrees").and_then(toml::Value::as_float).expect("Couldn't find inclination of a planet") ,
-                        body.get("mean_longitude_degrees").and_then(toml::Value::as_float).expect("Couldn't find mean longitude of a plaent") ,
-                        body.get("longitude_of_perhelion_degrees").and_then(toml::Value::as_float).expect("Couldn't find longitude of perigee of a planet") ,
-                        body.get("longitude_of_the_ascending_node_degrees").and_then(toml::Value::as_float).expect("Couldn't find ascending node of a planet") ,
-                        body.get("meanradius_km").and_then(toml::Value::as_float).expect("Couldn't find radius of a planet") ,
-                        body.get("mass_kg").and_then(toml::Value::as_float).expect("Couldn't find mass of a planet") 
-                    ], map_o_moons));
                 }
-                else {
-                    // Create a body without any moons/satellites
-                    system.insert(name.clone(), 
-                        Body::new(vec![body.get("semi_major_axis_km").and_then(toml::Value::as_float).expect("Couldn't find semimajor axis of a planet"), 
-                        body.get("eccentricity").and_then(toml::Value::as_float).expect("Couldn't find eccentricity of a planet") , 
-                        body.get("inclination_degrees").and_then(toml::Value::as_float).expect("Couldn't find inclination of a planet") ,
-                        body.get("mean_longitude_degrees").and_then(toml::Value::as_float).expect("Couldn't find mean longitude of a plaent") ,
-                        body.get("longitude_of_perihelion_degrees").and_then(toml::Value::as_float).expect("Couldn't find longitude of perigee of a planet") ,
-                        body.get("longitude_of_the_ascending_node_degrees").and_then(toml::Value::as_float).expect("Couldn't find ascending node of a planet") ,
-                        body.get("meanradius_km").and_then(toml::Value::as_float).expect("Couldn't find radius of a planet") ,
-                        body.get("mass_kg").and_then(toml::Value::as_float).expect("Couldn't find mass of a planet") 
-                    ]));
-                 }
+                chain.pop();
             }
-            // Pack and return the solar system
-            SolarSystem::new(system)
+            false
+        }
+        let mut chain = Vec::new();
+        search(&self.bodies, name, &mut chain).then_some(chain)
+    }
+
+    /// Names of every body in the system, moons and their satellites included, in alphabetical order
+    pub fn body_names(&self) -> Vec<String> {
+        fn collect(bodies: &HashMap<String, Body>, names: &mut Vec<String>) {
+            for (name, body) in bodies {
+                names.push(name.clone());
+                if let Some(moons) = body.moons.as_ref() {
+                    collect(moons, names);
+                }
+            }
+        }
+        let mut names = Vec::new();
+        collect(&self.bodies, &mut names);
+        names.sort();
+        names
+    }
+
+    /// Any body in the system by name, moons and their satellites included
+    pub fn find(&self, name: &str) -> Option<&Body> {
+        self.lineage(name).and_then(|chain| chain.last().copied())
+    }
+
+    /// Mass and zonal harmonics of whatever the named body orbits, its parent or the central body
+    pub fn parent_of(&self, name: &str) -> Option<(Mass, Option<&ZonalHarmonics>)> {
+        let lineage = self.lineage(name)?;
+        Some(match lineage.len() {
+            1 => (self.central_body.mass, self.central_body.zonal_harmonics.as_ref()),
+            n => (lineage[n - 2].orbit_data.mass, lineage[n - 2].zonal_harmonics.as_ref()),
+        })
+    }
+
+    /// Position (km) and velocity (km/s) of a body relative to what it orbits `time` past the epoch,
+    /// straight from its elements (see `Body::elements_at`) rather than the stored history
+    pub fn ephemeris(&self, name: &str, time: Time) -> Option<State> {
+        let body = self.find(name)?;
+        let (central_mass, central_harmonics) = self.parent_of(name)?;
+        let elements = body.elements_at(central_mass, central_harmonics, time);
+        Some(elements.to_state(get_mu(central_mass, body), Frame::ecliptic(&self.parent_name(name)?), time))
+    }
+
+    /// Heliocentric position (km) and velocity (km/s) of a body `time` past the epoch from its elements and its parents',
+    /// the `ephemeris` counterpart of `heliocentric_state`
+    pub fn heliocentric_ephemeris(&self, name: &str, time: Time) -> Option<State> {
+        let frame = Frame::ecliptic(&self.central_body.name);
+        if name == self.central_body.name {
+            return Some(State { frame, time, position: [0.0; 3], velocity: [0.0; 3] });
+        }
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        let (mut central_mass, mut central_harmonics) = (self.central_body.mass, self.central_body.zonal_harmonics.as_ref());
+        for body in self.lineage(name)? {
+            let mu = get_mu(central_mass, body);
+            let (body_position, body_velocity) = body.elements_at(central_mass, central_harmonics, time).to_state_vectors(mu);
+            position = add(position, body_position);
+            velocity = add(velocity, body_velocity);
+            central_mass = body.orbit_data.mass;
+            central_harmonics = body.zonal_harmonics.as_ref();
+        }
+        Some(State { frame, time, position, velocity })
+    }
+
+    /// Heliocentric position (km) and velocity (km/s) of a body at a stored sample, built by adding up
+    /// the states of the body and each of its parents. The Sun (central body) is always at the origin.
+    pub fn heliocentric_state(&self, name: &str, index: usize) -> Option<State> {
+        let frame = Frame::ecliptic(&self.central_body.name);
+        if name == self.central_body.name {
+            let time = *self.bodies.values().next()?.times.get(index)?;
+            return Some(State { frame, time, position: [0.0; 3], velocity: [0.0; 3] });
+        }
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        let lineage = self.lineage(name)?;
+        for body in &lineage {
+            position = add(position, *body.coords.get(index)?);
+            velocity = add(velocity, *body.vel.get(index)?);
+        }
+        let time = *lineage.last()?.times.get(index)?;
+        Some(State { frame, time, position, velocity })
+    }
+
+    /// Heliocentric states for every stored sample of a body
+    pub fn heliocentric_states(&self, name: &str) -> Option<Vec<State>> {
+        let samples = if name == self.central_body.name { self.sample_count() } else { self.find(name)?.coords.len() };
+        (0..samples).map(|index| self.heliocentric_state(name, index)).collect()
+    }
+
+    /// Position (km) and velocity (km/s) of `target` as seen from `observer` at a stored sample, in the ecliptic frame
+    /// centred on the observer
+    pub fn relative_state(&self, target: &str, observer: &str, index: usize) -> Option<State> {
+        let target_state = self.heliocentric_state(target, index)?;
+        let observer_state = self.heliocentric_state(observer, index)?;
+        Some(State {
+            frame: Frame::ecliptic(observer),
+            time: target_state.time,
+            position: sub(target_state.position, observer_state.position),
+            velocity: sub(target_state.velocity, observer_state.velocity),
+        })
+    }
+
+    /// Relative states for every stored sample both bodies have
+    pub fn relative_states(&self, target: &str, observer: &str) -> Option<Vec<State>> {
+        let samples = self.sample_count();
+        (0..samples).map(|index| self.relative_state(target, observer, index)).collect()
+    }
+
+    /// State of a body relative to what it orbits at a stored sample, in the ecliptic frame centred on its parent
+    pub fn state(&self, name: &str, index: usize) -> Option<State> {
+        let body = self.find(name)?;
+        Some(State {
+            frame: Frame::ecliptic(&self.parent_name(name)?),
+            time: *body.times.get(index)?,
+            position: *body.coords.get(index)?,
+            velocity: *body.vel.get(index)?,
+        })
+    }
+
+    /// Every stored state of a body relative to what it orbits, see `state`
+    pub fn states(&self, name: &str) -> Option<Vec<State>> {
+        (0..self.find(name)?.coords.len()).map(|index| self.state(name, index)).collect()
+    }
+
+    /// Osculating elements of a body around what it orbits at a stored sample, with `mu` and `h` filled in.
+    /// After an N-body run these show how far the orbit has been pulled from the elements it started with
+    pub fn osculating_elements(&self, name: &str, index: usize) -> Option<OrbitalElements> {
+        let body = self.find(name)?;
+        let (central_mass, _) = self.parent_of(name)?;
+        Some(OrbitalElements::from_state(&self.state(name, index)?, get_mu(central_mass, body), body.orbit_data.mass))
+    }
+
+    /// Number of samples every body has stored, bodies are always propagated together
+    pub fn sample_count(&self) -> usize {
+        self.bodies.values().map(|body| body.coords.len()).min().unwrap_or(0)
+    }
+}
+
+/// Where the data for the solar system is kept, relative to this crate
+pub const DEFAULT_DATA_PATH: &str = "../data/celestial_bodies_data.toml";
+
+impl SolarSystem {
+    /// Reads the TOML of data for bodies in the system at `path`, see `parse_system`
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| LoadError::Io { path: path.to_path_buf(), source })?;
+        parse_system(&contents, Some(path))
+    }
+}
+
+impl SolarSystem {
+    /// Builds the system from a schema record that has already passed `SystemRecord::validate`
+    pub fn from_record(record: &SystemRecord) -> Self {
+        let central_body = record.central_body.as_ref().map_or_else(CentralBody::default, |central| CentralBody {
+            name: central.name.clone(),
+            mass: Mass::kg(central.mass_kg),
+            radius: Length::km(central.meanradius_km),
+            zonal_harmonics: central.zonal_harmonics.as_ref().map(ZonalHarmonics::from_record),
+        });
+        let mut system = HashMap::new();
+        for (name, body) in &record.bodies {
+            let mut new_body = Body::from_record(body, BodyType::Planet);
+            // Place the body on its orbit
+            new_body.initialize_state(central_body.mass);
+            system.insert(name.clone(), new_body);
         }
-        Err(e) => panic!("Could not read Toml {}", e)
+        let valid_range = record.valid_from_jd.map(JulianDate::days).zip(record.valid_to_jd.map(JulianDate::days));
+        // Pack and return the solar system
+        SolarSystem::new(system, central_body, JulianDate::days(record.epoch_jd), valid_range)
     }
 }
 
+impl FromStr for SolarSystem {
+    type Err = LoadError;
 
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        parse_system(contents, None)
+    }
+}
 
+/// The text being loaded and where it came from, so errors can point at the right place
+struct Source<'a> {
+    contents: &'a str,
+    path: Option<&'a Path>,
+}
 
+impl Source<'_> {
+    /// Location of a table's header line, e.g. "SolarSystem.Earth.moons.Moon".
+    /// Tables written inline or with dotted keys have no header, so only the file is known for those
+    fn table_location(&self, table: &str) -> Location {
+        let header = format!("[{}]", table);
+        Location {
+            path: self.path.map(Path::to_path_buf),
+            line: self.contents.lines().position(|line| line.trim() == header).map(|index| index as u32 + 1),
+            column: None,
+        }
+    }
+}
+
+/// Tables that belong to a body rather than being bodies themselves
+const BODY_SUBTABLES: [&str; 5] = [".rates_per_century", ".zonal_harmonics", ".atmosphere", ".spacecraft", ".rotation"];
+
+/// Turns a failure to deserialize the schema into an error naming the body and field.
+/// The body is the one whose table header comes last before the problem
+fn schema_error(error: TomlError, source: &Source) -> LoadError {
+    let path = source.path.map(Path::to_path_buf);
+    let Some(span) = error.span() else {
+        return LoadError::Syntax { location: source.table_location(""), message: error.message().to_string() };
+    };
+    let location = Location::from_offset(path, source.contents, span.start);
+    let before = &source.contents[..span.start.min(source.contents.len())];
+    // Missing fields point at the table's own header, so the search includes the rest of that line
+    let line_end = source.contents[before.len()..].find('\n').map_or(source.contents.len(), |newline| before.len() + newline);
+    let body = source.contents[..line_end]
+        .lines()
+        .rev()
+        .filter_map(|line| line.trim().strip_prefix("[SolarSystem.")?.strip_suffix(']'))
+        .next()
+        .and_then(|table| BODY_SUBTABLES.iter().fold(table, |table, subtable| table.trim_end_matches(subtable)).rsplit('.').next())
+        .map(str::to_string);
+    let message = error.message().trim().to_string();
+    // serde puts the field name in backticks for missing and unknown fields
+    let quoted_field = message.split('`').nth(1).map(str::to_string);
+    if message.starts_with("missing field") {
+        return LoadError::MissingField { body, field: quoted_field.unwrap_or_default(), location };
+    }
+    // Otherwise the problem is with the value on the line the span points at
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = source.contents[line_start..].lines().next().unwrap_or("");
+    let field = if message.starts_with("unknown field") {
+        quoted_field.unwrap_or_default()
+    }
+    else {
+        line.split('=').next().unwrap_or("").trim().to_string()
+    };
+    LoadError::InvalidField { body, field, reason: message, location }
+}
+
+/// More comments throughout but reades the toml of data for bodies in the system, packs the structs,
+/// and returns a full instance of SolarSystem. path is only used to say where errors are
+fn parse_system(contents: &str, path: Option<&Path>) -> Result<SolarSystem, LoadError> {
+    let source = Source { contents, path };
+    // Catch plain syntax errors first so they aren't mistaken for problems with a field
+    toml::from_str::<Table>(contents).map_err(|e: TomlError| LoadError::Syntax {
+        location: match e.span() {
+            Some(span) => Location::from_offset(path.map(Path::to_path_buf), contents, span.start),
+            None => source.table_location(""),
+        },
+        message: e.message().to_string(),
+    })?;
+    let record: SystemRecord = toml::from_str(contents).map_err(|e| schema_error(e, &source))?;
+    record.validate().map_err(|violation| LoadError::InvalidField {
+        location: source.table_location(&violation.table),
+        body: violation.body,
+        field: violation.field,
+        reason: violation.reason,
+    })?;
+
+    Ok(SolarSystem::from_record(&record))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    const MU: GravitationalParameter = GravitationalParameter::km3_per_s2(398600.4418);
+
+    /// Difference between two angles, between -pi and pi
+    fn angle_between(a: Angle, b: Angle) -> f64 {
+        let difference = wrap_angle((a - b).in_radians());
+        if difference > PI { difference - TAU } else { difference }
+    }
+
+    fn assert_round_trip(data: [f64; 7]) {
+        let elements = OrbitalElements::new(data);
+        let (position, velocity) = elements.to_state_vectors(MU);
+        let back = OrbitalElements::from_state_vectors(position, velocity, MU, elements.mass);
+        let relative = ((back.semimajor_axis - elements.semimajor_axis) / elements.semimajor_axis).abs();
+        assert!(relative < 1e-12, "semimajor axis {} came back as {}", elements.semimajor_axis, back.semimajor_axis);
+        assert!((back.eccentricity - elements.eccentricity).abs() < 1e-12, "eccentricity {} came back as {}", elements.eccentricity, back.eccentricity);
+        for (name, before, after) in [
+            ("inclination", elements.inclination, back.inclination),
+            ("node", elements.longitude_of_ascending_node, back.longitude_of_ascending_node),
+            ("perigee", elements.argument_of_parigee, back.argument_of_parigee),
+            ("mean anomaly", elements.mean_anomoly, back.mean_anomoly),
+        ] {
+            assert!(angle_between(after, before).abs() < 1e-9, "{} {} came back as {} for {:?}", name, before, after, data);
+        }
+    }
+
+    #[test]
+    fn elliptic_elements_survive_a_state_round_trip() {
+        assert_round_trip([7000.0, 0.01, 51.6, 120.0, 45.0, 300.0, 1000.0]);
+        assert_round_trip([26600.0, 0.74, 63.4, 250.0, 270.0, 10.0, 1000.0]);
+        assert_round_trip([42164.0, 0.3, 170.0, 15.0, 200.0, 180.0, 1000.0]);
+    }
+
+    #[test]
+    fn hyperbolic_elements_survive_a_state_round_trip() {
+        assert_round_trip([-20000.0, 1.5, 30.0, 80.0, 100.0, 20.0, 1000.0]);
+        assert_round_trip([-5000.0, 3.0, 100.0, 300.0, 10.0, 340.0, 1000.0]);
+    }
+
+    #[test]
+    fn degenerate_orbits_keep_their_state() {
+        // Circular and equatorial orbits lose the undefined angles but must still land on the same state
+        for data in [[7000.0, 0.0, 30.0, 40.0, 50.0, 60.0, 1000.0], [7000.0, 0.2, 0.0, 40.0, 50.0, 60.0, 1000.0], [7000.0, 0.0, 0.0, 40.0, 50.0, 60.0, 1000.0]] {
+            let (position, velocity) = OrbitalElements::new(data).to_state_vectors(MU);
+            let back = OrbitalElements::from_state_vectors(position, velocity, MU, Mass::kg(1000.0));
+            let (position_back, velocity_back) = back.to_state_vectors(MU);
+            assert!(norm(sub(position_back, position)) < 1e-6, "position moved for {:?}", data);
+            assert!(norm(sub(velocity_back, velocity)) < 1e-9, "velocity moved for {:?}", data);
+        }
+    }
+}
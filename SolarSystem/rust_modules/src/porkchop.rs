@@ -0,0 +1,126 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::epoch::DateRange;
+use crate::lambert::{solve_lambert, LambertBranch};
+use crate::orbit_propagration::gravitational_parameter;
+use crate::planet::SolarSystem;
+use crate::units::{JulianDate, SpecificEnergy, Speed, Time};
+use crate::vector::{norm, sub};
+
+/// The cheapest transfer for one pair of departure and arrival dates
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PorkchopPoint {
+    pub departure_jd: JulianDate,
+    pub arrival_jd: JulianDate,
+    pub time_of_flight: Time,
+    pub c3: SpecificEnergy, // square of the departure hyperbolic excess speed
+    pub departure_v_infinity: Speed,
+    pub arrival_v_infinity: Speed,
+    pub total_delta_v: Speed, // departure plus arrival hyperbolic excess speeds
+    pub revolutions: u32,
+    pub branch: LambertBranch,
+}
+
+/// A grid of transfers between two bodies, ready to be written out for contour plots
+#[derive(Serialize, Debug, Clone)]
+pub struct Porkchop {
+    pub departure_body: String,
+    pub arrival_body: String,
+    pub center: String,
+    pub points: Vec<PorkchopPoint>,
+}
+
+impl Porkchop {
+    /// Solves Lambert's problem around the central body for every departure and arrival date pair where the arrival
+    /// comes after the departure, keeping the prograde solution with the least total delta v among up to
+    /// `max_revolutions` whole revolutions. Bodies follow their elements (see `SolarSystem::heliocentric_ephemeris`).
+    /// None if either body isn't in the system
+    pub fn new(
+        system: &SolarSystem,
+        departure_body: &str,
+        arrival_body: &str,
+        departures: DateRange,
+        arrivals: DateRange,
+        max_revolutions: u32,
+    ) -> Option<Self> {
+        let state = |name: &str, julian_date: JulianDate| system.heliocentric_ephemeris(name, julian_date - system.epoch);
+        // Checked up front so an empty date range still turns away unknown bodies
+        state(departure_body, system.epoch)?;
+        state(arrival_body, system.epoch)?;
+        let mu = gravitational_parameter(system.central_body.mass);
+        let arrival_dates = arrivals.dates();
+        let mut points = Vec::new();
+        for departure_jd in departures.dates() {
+            let departure = state(departure_body, departure_jd)?;
+            for &arrival_jd in arrival_dates.iter().filter(|arrival_jd| **arrival_jd > departure_jd) {
+                let arrival = state(arrival_body, arrival_jd)?;
+                let best = solve_lambert(&departure, &arrival, mu, true, max_revolutions)
+                    .into_iter()
+                    .map(|solution| {
+                        // Every state here is in the central body's ecliptic frame
+                        let departure_v_infinity = Speed::km_per_s(norm(sub(solution.departure.velocity, departure.velocity)));
+                        let arrival_v_infinity = Speed::km_per_s(norm(sub(solution.arrival.velocity, arrival.velocity)));
+                        PorkchopPoint {
+                            departure_jd,
+                            arrival_jd,
+                            time_of_flight: arrival_jd - departure_jd,
+                            c3: departure_v_infinity * departure_v_infinity,
+                            departure_v_infinity,
+                            arrival_v_infinity,
+                            total_delta_v: departure_v_infinity + arrival_v_infinity,
+                            revolutions: solution.revolutions,
+                            branch: solution.branch,
+                        }
+                    })
+                    .filter(|point| point.total_delta_v.in_km_per_s().is_finite())
+                    .min_by(|a, b| a.total_delta_v.total_cmp(&b.total_delta_v));
+                points.extend(best);
+            }
+        }
+        Some(Self {
+            departure_body: departure_body.to_string(),
+            arrival_body: arrival_body.to_string(),
+            center: system.central_body.name.clone(),
+            points,
+        })
+    }
+
+    /// The point with the least total delta v, if any transfer was found
+    pub fn best(&self) -> Option<&PorkchopPoint> {
+        self.points.iter().min_by(|a, b| a.total_delta_v.total_cmp(&b.total_delta_v))
+    }
+
+    /// Comma separated values, one row per date pair. Lines starting with # before the column names give the bodies and units
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "# {} to {} around {}", self.departure_body, self.arrival_body, self.center)?;
+        writeln!(writer, "# units: dates Julian date (TDB), c3 km^2/s^2, speeds km/s")?;
+        writeln!(
+            writer,
+            "departure_jd,arrival_jd,time_of_flight_days,c3_km2_s2,departure_v_infinity_km_s,arrival_v_infinity_km_s,total_delta_v_km_s,revolutions,branch"
+        )?;
+        for point in &self.points {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{:?}",
+                point.departure_jd.in_days(),
+                point.arrival_jd.in_days(),
+                point.time_of_flight.in_days(),
+                point.c3.in_km2_per_s2(),
+                point.departure_v_infinity.in_km_per_s(),
+                point.arrival_v_infinity.in_km_per_s(),
+                point.total_delta_v.in_km_per_s(),
+                point.revolutions,
+                point.branch
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Pretty printed JSON of the whole grid
+    pub fn write_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *writer, self)?;
+        writeln!(writer)
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+use crate::horizons::slope;
+use crate::orbit_propagration::get_mu;
+use crate::planet::{OrbitalElements, SolarSystem};
+use crate::units::{Acceleration, AngularRate, GravitationalParameter, Position, Velocity};
+use crate::vector::{add, dot, norm, scale};
+
+/// Speed of light in km/s
+pub const SPEED_OF_LIGHT: f64 = 299792.458;
+
+/// First order post-Newtonian (Schwarzschild) acceleration of a body at `position` moving at `velocity`
+/// relative to a mass with gravitational parameter mu, in the test particle limit:
+///     mu / (c^2 r^3) * ((4 mu / r - v^2) r + 4 (r . v) v)
+pub fn schwarzschild_acceleration(mu: GravitationalParameter, position: Position, velocity: Velocity) -> Acceleration {
+    let (mu, position, velocity) = (mu.in_km3_per_s2(), position.in_km(), velocity.in_km_per_s());
+    let r = norm(position);
+    let v_squared = dot(velocity, velocity);
+    let factor = mu / (SPEED_OF_LIGHT * SPEED_OF_LIGHT * r.powi(3));
+    Acceleration::km_per_s2(scale(
+        add(scale(position, 4.0 * mu / r - v_squared), scale(velocity, 4.0 * dot(position, velocity))),
+        factor,
+    ))
+}
+
+/// How fast a body's perihelion (perigee for moons) moved over the stored samples.
+/// The longitude of perihelion of the osculating orbit at each sample is unwrapped and fitted with a least squares line,
+/// so the run should cover a few orbits for short period terms to average out. This is the total apsidal precession,
+/// mostly from the other planets' pull (several hundred "/century for Mercury), so only the difference from a Newtonian run
+/// isolates the general relativistic part, see `relativistic_perihelion_advance`.
+/// None if the body isn't in the system or has fewer than two samples
+pub fn perihelion_advance(system: &SolarSystem, name: &str) -> Option<AngularRate> {
+    let body = system.find(name)?;
+    let (central_mass, _) = system.parent_of(name)?;
+    let mu = get_mu(central_mass, body);
+    let mut points = Vec::with_capacity(body.coords.len());
+    let mut previous: Option<f64> = None;
+    for ((position, velocity), time) in body.coords.iter().zip(&body.vel).zip(&body.times) {
+        let elements = OrbitalElements::from_state_vectors(*position, *velocity, mu, body.orbit_data.mass);
+        let mut longitude = (elements.longitude_of_ascending_node + elements.argument_of_parigee).in_radians();
+        // Keep the angle continuous across the wrap at 2 pi
+        if let Some(previous) = previous {
+            longitude += ((previous - longitude) / std::f64::consts::TAU).round() * std::f64::consts::TAU;
+        }
+        previous = Some(longitude);
+        points.push((time.in_seconds(), longitude));
+    }
+    (points.len() >= 2).then(|| AngularRate::radians_per_s(slope(&points)))
+}
+
+/// `perihelion_advance` of every planet in the system, by name
+pub fn perihelion_advances(system: &SolarSystem) -> BTreeMap<String, AngularRate> {
+    system
+        .bodies
+        .keys()
+        .filter_map(|name| Some((name.clone(), perihelion_advance(system, name)?)))
+        .collect()
+}
+
+/// The part of a body's perihelion advance due to general relativity: `perihelion_advance`
+/// in a run with `ForceModel::relativity` on less the same in a run with it off. Both systems should start from the same
+/// states and be propagated over the same span with the same integrator and step, about 43"/century for Mercury.
+/// None if either run doesn't give an advance for the body
+pub fn relativistic_perihelion_advance(relativistic: &SolarSystem, newtonian: &SolarSystem, name: &str) -> Option<AngularRate> {
+    Some(perihelion_advance(relativistic, name)? - perihelion_advance(newtonian, name)?)
+}
@@ -0,0 +1,461 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::epoch::J2000;
+
+/// Layout of celestial_bodies_data.toml, bodies are read straight into these and checked by `validate`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SystemRecord {
+    pub number_of_bodies: usize,
+    #[serde(default = "default_epoch")]
+    pub epoch_jd: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from_jd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_to_jd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub central_body: Option<CentralBodyRecord>, // the Sun when left out
+    #[serde(rename = "SolarSystem", alias = "solar_system")]
+    pub bodies: BTreeMap<String, BodyRecord>,
+}
+
+fn default_epoch() -> f64 {
+    J2000.in_days()
+}
+
+/// One planet, moon or satellite. Moons are nested under their parent in the same layout, to any depth
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BodyRecord {
+    #[serde(alias = "semimajor_axis_km")]
+    pub semi_major_axis_km: f64,
+    pub eccentricity: f64,
+    pub inclination_degrees: f64,
+    pub mean_longitude_degrees: f64,
+    #[serde(alias = "longitude_of_perhelion_degrees")]
+    pub longitude_of_perihelion_degrees: f64,
+    #[serde(alias = "longitude_of_ascending_node_degrees")]
+    pub longitude_of_the_ascending_node_degrees: f64,
+    #[serde(alias = "mean_radius_km", alias = "radius_km")]
+    pub meanradius_km: f64,
+    pub mass_kg: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rates_per_century: Option<RatesRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zonal_harmonics: Option<ZonalHarmonicsRecord>, // of this body's own field, felt by its moons
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atmosphere: Option<AtmosphereRecord>, // felt as drag by its moons
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spacecraft: Option<SpacecraftRecord>, // for satellites that feel radiation pressure and drag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<RotationRecord>, // for frames fixed to the body
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub moons: BTreeMap<String, BodyRecord>,
+}
+
+/// Whatever the bodies orbit, it sits at the origin so only its size matters
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CentralBodyRecord {
+    pub name: String,
+    pub mass_kg: f64,
+    #[serde(alias = "mean_radius_km", alias = "radius_km")]
+    pub meanradius_km: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zonal_harmonics: Option<ZonalHarmonicsRecord>,
+}
+
+/// Zonal harmonic coefficients of a gravity field, unnormalized. The pole is given in ecliptic
+/// longitude and latitude and defaults to the z axis of the system's frame
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ZonalHarmonicsRecord {
+    pub reference_radius_km: f64,
+    pub j2: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub j3: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub j4: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pole_longitude_degrees: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pole_latitude_degrees: Option<f64>,
+}
+
+/// How a body spins. The pole is given in ecliptic longitude and latitude and defaults to the z axis of the
+/// system's frame, the prime meridian is its angle from the equator's ascending node at the system epoch
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RotationRecord {
+    pub period_hours: f64, // sidereal, negative for retrograde spin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prime_meridian_degrees: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pole_longitude_degrees: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pole_latitude_degrees: Option<f64>,
+}
+
+/// Surface properties of an artificial satellite
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SpacecraftRecord {
+    pub area_m2: f64,
+    pub reflectivity_coefficient: f64,
+    pub drag_coefficient: f64,
+}
+
+/// A planet's atmosphere, either exponential (reference altitude and density with a scale height)
+/// or a table of densities at increasing altitudes
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AtmosphereRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_altitude_km: Option<f64>, // 0 when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_density_kg_m3: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_height_km: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitudes_km: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub densities_kg_m3: Option<Vec<f64>>,
+}
+
+/// Element rates per Julian century, same names and units as the elements themselves
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RatesRecord {
+    #[serde(alias = "semimajor_axis_km")]
+    pub semi_major_axis_km: f64,
+    pub eccentricity: f64,
+    pub inclination_degrees: f64,
+    pub mean_longitude_degrees: f64,
+    #[serde(alias = "longitude_of_perhelion_degrees")]
+    pub longitude_of_perihelion_degrees: f64,
+    #[serde(alias = "longitude_of_ascending_node_degrees")]
+    pub longitude_of_the_ascending_node_degrees: f64,
+}
+
+/// A value that loaded fine but makes no sense
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    pub table: String, // TOML path of the table holding the field, empty for the top of the file
+    pub body: Option<String>,
+    pub field: String,
+    pub reason: String,
+}
+
+impl SystemRecord {
+    /// Checks every value after loading, returning the first problem found
+    pub fn validate(&self) -> Result<(), SchemaViolation> {
+        if self.number_of_bodies != self.bodies.len() {
+            return Err(SchemaViolation {
+                table: String::new(),
+                body: None,
+                field: "number_of_bodies".to_string(),
+                reason: format!("says {} but there are {} bodies", self.number_of_bodies, self.bodies.len()),
+            });
+        }
+        if let (Some(from), Some(to)) = (self.valid_from_jd, self.valid_to_jd) {
+            if from > to {
+                return Err(SchemaViolation {
+                    table: String::new(),
+                    body: None,
+                    field: "valid_to_jd".to_string(),
+                    reason: format!("{} is before valid_from_jd {}", to, from),
+                });
+            }
+        }
+        if self.valid_from_jd.is_some() != self.valid_to_jd.is_some() {
+            let missing = if self.valid_from_jd.is_none() { "valid_from_jd" } else { "valid_to_jd" };
+            return Err(SchemaViolation {
+                table: String::new(),
+                body: None,
+                field: missing.to_string(),
+                reason: "both ends of the valid range are needed".to_string(),
+            });
+        }
+        if let Some(central) = &self.central_body {
+            for (field, value) in [("mass_kg", central.mass_kg), ("meanradius_km", central.meanradius_km)] {
+                if !(value.is_finite() && value > 0.0) {
+                    return Err(SchemaViolation {
+                        table: "central_body".to_string(),
+                        body: Some(central.name.clone()),
+                        field: field.to_string(),
+                        reason: format!("{} must be positive", value),
+                    });
+                }
+            }
+            if let Some(harmonics) = &central.zonal_harmonics {
+                harmonics.validate(&central.name, "central_body.zonal_harmonics")?;
+            }
+        }
+        for (name, body) in &self.bodies {
+            body.validate(name, &format!("SolarSystem.{}", name))?;
+        }
+        Ok(())
+    }
+}
+
+impl BodyRecord {
+    /// Checks this body and its moons, table is the TOML path of this body's table
+    fn validate(&self, name: &str, table: &str) -> Result<(), SchemaViolation> {
+        let violation = |field: &str, reason: String| SchemaViolation {
+            table: table.to_string(),
+            body: Some(name.to_string()),
+            field: field.to_string(),
+            reason,
+        };
+        for (field, value) in [
+            ("semi_major_axis_km", self.semi_major_axis_km),
+            ("eccentricity", self.eccentricity),
+            ("inclination_degrees", self.inclination_degrees),
+            ("mean_longitude_degrees", self.mean_longitude_degrees),
+            ("longitude_of_perihelion_degrees", self.longitude_of_perihelion_degrees),
+            ("longitude_of_the_ascending_node_degrees", self.longitude_of_the_ascending_node_degrees),
+            ("meanradius_km", self.meanradius_km),
+            ("mass_kg", self.mass_kg),
+        ] {
+            if !value.is_finite() {
+                return Err(violation(field, format!("{} is not a finite number", value)));
+            }
+        }
+        let e = self.eccentricity;
+        if e < 0.0 {
+            return Err(violation("eccentricity", format!("{} is negative", e)));
+        }
+        if e == 1.0 {
+            return Err(violation("eccentricity", "parabolic orbits have no semimajor axis".to_string()));
+        }
+        if e < 1.0 && self.semi_major_axis_km <= 0.0 {
+            return Err(violation("semi_major_axis_km", format!("{} must be positive for an elliptic orbit", self.semi_major_axis_km)));
+        }
+        if e > 1.0 && self.semi_major_axis_km >= 0.0 {
+            return Err(violation("semi_major_axis_km", format!("{} must be negative for a hyperbolic orbit", self.semi_major_axis_km)));
+        }
+        if self.mass_kg <= 0.0 {
+            return Err(violation("mass_kg", format!("{} must be positive", self.mass_kg)));
+        }
+        if self.meanradius_km <= 0.0 {
+            return Err(violation("meanradius_km", format!("{} must be positive", self.meanradius_km)));
+        }
+        if !(-180.0..=180.0).contains(&self.inclination_degrees) {
+            return Err(violation("inclination_degrees", format!("{} is outside -180 to 180", self.inclination_degrees)));
+        }
+        for (field, angle) in [
+            ("mean_longitude_degrees", self.mean_longitude_degrees),
+            ("longitude_of_perihelion_degrees", self.longitude_of_perihelion_degrees),
+            ("longitude_of_the_ascending_node_degrees", self.longitude_of_the_ascending_node_degrees),
+        ] {
+            if !(-360.0..=360.0).contains(&angle) {
+                return Err(violation(field, format!("{} is outside -360 to 360", angle)));
+            }
+        }
+        if let Some(harmonics) = &self.zonal_harmonics {
+            harmonics.validate(name, &format!("{}.zonal_harmonics", table))?;
+        }
+        if let Some(atmosphere) = &self.atmosphere {
+            atmosphere.validate(name, &format!("{}.atmosphere", table))?;
+        }
+        if let Some(rotation) = &self.rotation {
+            rotation.validate(name, &format!("{}.rotation", table))?;
+        }
+        if let Some(spacecraft) = &self.spacecraft {
+            let table = format!("{}.spacecraft", table);
+            for (field, value, positive) in [
+                ("area_m2", spacecraft.area_m2, true),
+                ("reflectivity_coefficient", spacecraft.reflectivity_coefficient, false),
+                ("drag_coefficient", spacecraft.drag_coefficient, false),
+            ] {
+                if !value.is_finite() || value < 0.0 || (positive && value == 0.0) {
+                    let bound = if positive { "positive" } else { "zero or more" };
+                    return Err(SchemaViolation {
+                        table,
+                        body: Some(name.to_string()),
+                        field: field.to_string(),
+                        reason: format!("{} must be {}", value, bound),
+                    });
+                }
+            }
+        }
+        for (moon_name, moon) in &self.moons {
+            moon.validate(moon_name, &format!("{}.moons.{}", table, moon_name))?;
+        }
+        Ok(())
+    }
+}
+
+impl ZonalHarmonicsRecord {
+    /// Checks the coefficients of `body`'s field, table is the TOML path of the harmonics table
+    fn validate(&self, body: &str, table: &str) -> Result<(), SchemaViolation> {
+        let violation = |field: &str, reason: String| SchemaViolation {
+            table: table.to_string(),
+            body: Some(body.to_string()),
+            field: field.to_string(),
+            reason,
+        };
+        if !(self.reference_radius_km.is_finite() && self.reference_radius_km > 0.0) {
+            return Err(violation("reference_radius_km", format!("{} must be positive", self.reference_radius_km)));
+        }
+        for (field, value) in [("j2", Some(self.j2)), ("j3", self.j3), ("j4", self.j4)] {
+            if value.is_some_and(|value| !value.is_finite()) {
+                return Err(violation(field, format!("{} is not a finite number", value.unwrap_or_default())));
+            }
+        }
+        check_pole(self.pole_longitude_degrees, self.pole_latitude_degrees).map_err(|(field, reason)| violation(field, reason))
+    }
+}
+
+/// Checks an optional pole direction has both angles or neither and that they are in range,
+/// giving the field at fault and why
+fn check_pole(longitude: Option<f64>, latitude: Option<f64>) -> Result<(), (&'static str, String)> {
+    match (longitude, latitude) {
+        (Some(_), None) => Err(("pole_latitude_degrees", "both pole angles are needed".to_string())),
+        (None, Some(_)) => Err(("pole_longitude_degrees", "both pole angles are needed".to_string())),
+        (_, Some(latitude)) if !(-90.0..=90.0).contains(&latitude) => {
+            Err(("pole_latitude_degrees", format!("{} is outside -90 to 90", latitude)))
+        }
+        (Some(longitude), _) if !longitude.is_finite() => {
+            Err(("pole_longitude_degrees", format!("{} is not a finite number", longitude)))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl RotationRecord {
+    /// Checks the spin of `body`, table is the TOML path of the rotation table
+    fn validate(&self, body: &str, table: &str) -> Result<(), SchemaViolation> {
+        let violation = |field: &str, reason: String| SchemaViolation {
+            table: table.to_string(),
+            body: Some(body.to_string()),
+            field: field.to_string(),
+            reason,
+        };
+        if !(self.period_hours.is_finite() && self.period_hours != 0.0) {
+            return Err(violation("period_hours", format!("{} must be a non-zero number", self.period_hours)));
+        }
+        if self.prime_meridian_degrees.is_some_and(|angle| !angle.is_finite()) {
+            return Err(violation("prime_meridian_degrees", "is not a finite number".to_string()));
+        }
+        check_pole(self.pole_longitude_degrees, self.pole_latitude_degrees).map_err(|(field, reason)| violation(field, reason))
+    }
+}
+
+impl AtmosphereRecord {
+    /// Checks the atmosphere of `body` has exactly one usable form, table is the TOML path of the atmosphere table
+    fn validate(&self, body: &str, table: &str) -> Result<(), SchemaViolation> {
+        let violation = |field: &str, reason: String| SchemaViolation {
+            table: table.to_string(),
+            body: Some(body.to_string()),
+            field: field.to_string(),
+            reason,
+        };
+        match (self.scale_height_km, &self.altitudes_km, &self.densities_kg_m3) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+                Err(violation("scale_height_km", "give either a scale height or a table of densities, not both".to_string()))
+            }
+            (Some(scale_height), None, None) => {
+                if !(scale_height.is_finite() && scale_height > 0.0) {
+                    return Err(violation("scale_height_km", format!("{} must be positive", scale_height)));
+                }
+                let Some(density) = self.reference_density_kg_m3 else {
+                    return Err(violation("reference_density_kg_m3", "an exponential atmosphere needs a reference density".to_string()));
+                };
+                if !(density.is_finite() && density >= 0.0) {
+                    return Err(violation("reference_density_kg_m3", format!("{} must be zero or more", density)));
+                }
+                if self.reference_altitude_km.is_some_and(|altitude| !altitude.is_finite()) {
+                    return Err(violation("reference_altitude_km", "is not a finite number".to_string()));
+                }
+                Ok(())
+            }
+            (None, Some(altitudes), Some(densities)) => {
+                if self.reference_density_kg_m3.is_some() || self.reference_altitude_km.is_some() {
+                    return Err(violation("reference_density_kg_m3", "only used with a scale height".to_string()));
+                }
+                if altitudes.len() != densities.len() || altitudes.len() < 2 {
+                    return Err(violation(
+                        "densities_kg_m3",
+                        format!("needs one density per altitude and at least two, found {} and {}", densities.len(), altitudes.len()),
+                    ));
+                }
+                if altitudes.windows(2).any(|pair| !(pair[0].is_finite() && pair[1] > pair[0])) {
+                    return Err(violation("altitudes_km", "must be finite and strictly increasing".to_string()));
+                }
+                if let Some(density) = densities.iter().find(|density| !(density.is_finite() && **density > 0.0)) {
+                    return Err(violation("densities_kg_m3", format!("{} must be positive", density)));
+                }
+                Ok(())
+            }
+            (None, None, _) => Err(violation("scale_height_km", "an atmosphere needs a scale height or a table of densities".to_string())),
+            (None, Some(_), None) => Err(violation("densities_kg_m3", "a table of altitudes needs its densities".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Earth with the Moon nested under it, in the data file's layout
+    const EARTH_AND_MOON: &str = "number_of_bodies = 1
+
+[SolarSystem.Earth]
+semi_major_axis_km = 149598023.0
+eccentricity = 0.0167
+inclination_degrees = 0.0
+mean_longitude_degrees = 100.46
+longitude_of_perihelion_degrees = 102.94
+longitude_of_the_ascending_node_degrees = 0.0
+meanradius_km = 6371.0
+mass_kg = 5.972E24
+
+[SolarSystem.Earth.moons.Moon]
+semi_major_axis_km = 384400.0
+eccentricity = 0.0549
+inclination_degrees = 5.145
+mean_longitude_degrees = 218.3
+longitude_of_perihelion_degrees = 83.4
+longitude_of_the_ascending_node_degrees = 125.1
+meanradius_km = 1737.4
+mass_kg = 7.342E22
+";
+
+    fn record(contents: &str) -> SystemRecord {
+        toml::from_str(contents).expect("record should deserialize")
+    }
+
+    #[test]
+    fn shipped_data_and_nested_moons_are_valid() {
+        let shipped = std::fs::read_to_string(crate::planet::DEFAULT_DATA_PATH).expect("data file should be readable");
+        assert_eq!(record(&shipped).validate(), Ok(()));
+        let nested = record(EARTH_AND_MOON);
+        assert_eq!(nested.validate(), Ok(()));
+        assert!(nested.bodies["Earth"].moons.contains_key("Moon"));
+        assert_eq!(nested.epoch_jd, J2000.in_days(), "the epoch defaults to J2000");
+    }
+
+    #[test]
+    fn violations_name_the_table_body_and_field() {
+        let miscounted = record(&EARTH_AND_MOON.replace("number_of_bodies = 1", "number_of_bodies = 2"));
+        let violation = miscounted.validate().expect_err("two bodies claimed, one given");
+        assert_eq!((violation.table.as_str(), violation.body, violation.field.as_str()), ("", None, "number_of_bodies"));
+
+        let moon = record(&EARTH_AND_MOON.replace("eccentricity = 0.0549", "eccentricity = -0.0549"));
+        let violation = moon.validate().expect_err("negative eccentricity");
+        assert_eq!(violation.table, "SolarSystem.Earth.moons.Moon");
+        assert_eq!(violation.body.as_deref(), Some("Moon"));
+        assert_eq!(violation.field, "eccentricity");
+    }
+
+    #[test]
+    fn unknown_fields_are_refused_and_old_spellings_accepted() {
+        let misspelt = EARTH_AND_MOON.replace("mass_kg = 5.972E24", "mass_kg = 5.972E24\nmas_kg = 1.0");
+        assert!(toml::from_str::<SystemRecord>(&misspelt).is_err());
+        let old = EARTH_AND_MOON.replace("semi_major_axis_km = 384400.0", "semimajor_axis_km = 384400.0");
+        assert_eq!(record(&old).bodies["Earth"].moons["Moon"].semi_major_axis_km, 384400.0);
+    }
+}
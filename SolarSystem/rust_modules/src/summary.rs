@@ -0,0 +1,180 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::orbit_propagration::get_mu;
+use crate::planet::SolarSystem;
+use crate::units::{Angle, AngularRate, JulianDate, Length, SpecificAngularMomentum, SpecificEnergy, Speed, Time};
+
+/// Size, shape and timing of one body's orbit at the epoch, worked out from its elements
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OrbitSummary {
+    pub name: String,
+    pub parent: String,
+    pub semimajor_axis: Length,
+    pub eccentricity: f64,
+    pub inclination: Angle, // between 0 and 180 degrees
+    pub period: Option<Time>, // None for orbits that never come back
+    pub mean_motion: AngularRate,
+    pub periapsis: Length,
+    pub apoapsis: Option<Length>,
+    pub periapsis_speed: Speed,
+    pub apoapsis_speed: Option<Speed>,
+    pub semi_latus_rectum: Length,
+    pub specific_energy: SpecificEnergy,
+    pub specific_angular_momentum: SpecificAngularMomentum,
+}
+
+/// `OrbitSummary` of every body and moon in the system, in alphabetical order
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SystemSummary {
+    pub central_body: String,
+    pub epoch_jd: JulianDate,
+    pub bodies: Vec<OrbitSummary>,
+}
+
+impl SolarSystem {
+    /// Derived quantities of a body's orbit around whatever it orbits, from its elements at the epoch.
+    /// None if it isn't in the system
+    pub fn orbit_summary(&self, name: &str) -> Option<OrbitSummary> {
+        let body = self.find(name)?;
+        let (central_mass, _) = self.parent_of(name)?;
+        let mu = get_mu(central_mass, body);
+        let elements = body.orbit_data.with_gravitational_parameter(mu);
+        Some(OrbitSummary {
+            name: name.to_string(),
+            parent: self.parent_name(name)?,
+            semimajor_axis: elements.semimajor_axis,
+            eccentricity: elements.eccentricity,
+            inclination: elements.inclination,
+            period: elements.period(mu),
+            mean_motion: elements.mean_motion(mu),
+            periapsis: elements.periapsis_radius(),
+            apoapsis: elements.apoapsis_radius(),
+            periapsis_speed: elements.periapsis_speed(mu),
+            apoapsis_speed: elements.apoapsis_speed(mu),
+            semi_latus_rectum: elements.semi_latus_rectum(),
+            specific_energy: elements.specific_energy()?,
+            specific_angular_momentum: elements.h?,
+        })
+    }
+
+    /// `orbit_summary` of every body in the system, moons included
+    pub fn summary(&self) -> SystemSummary {
+        SystemSummary {
+            central_body: self.central_body.name.clone(),
+            epoch_jd: self.epoch,
+            bodies: self.body_names().iter().filter_map(|name| self.orbit_summary(name)).collect(),
+        }
+    }
+}
+
+impl SystemSummary {
+    /// Comma separated values, one row per body with the columns left empty where an orbit never comes back.
+    /// Lines starting with # before the column names give the epoch and units
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        let optional = |value: Option<f64>| value.map_or_else(String::new, |value| value.to_string());
+        writeln!(writer, "# orbits around {} at epoch JD {}", self.central_body, self.epoch_jd.in_days())?;
+        writeln!(writer, "# units: distances km, angles degrees, periods days, speeds km/s")?;
+        writeln!(
+            writer,
+            "name,parent,semimajor_axis_km,eccentricity,inclination_deg,period_days,mean_motion_deg_per_day,periapsis_km,apoapsis_km,\
+             periapsis_speed_km_s,apoapsis_speed_km_s,semi_latus_rectum_km,specific_energy_km2_s2,specific_angular_momentum_km2_s"
+        )?;
+        for orbit in &self.bodies {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                orbit.name,
+                orbit.parent,
+                orbit.semimajor_axis.in_km(),
+                orbit.eccentricity,
+                orbit.inclination.in_degrees(),
+                optional(orbit.period.map(Time::in_days)),
+                orbit.mean_motion.in_degrees_per_day(),
+                orbit.periapsis.in_km(),
+                optional(orbit.apoapsis.map(Length::in_km)),
+                orbit.periapsis_speed.in_km_per_s(),
+                optional(orbit.apoapsis_speed.map(Speed::in_km_per_s)),
+                orbit.semi_latus_rectum.in_km(),
+                orbit.specific_energy.in_km2_per_s2(),
+                orbit.specific_angular_momentum.in_km2_per_s()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Pretty printed JSON of every orbit, in km, radians, seconds and km/s
+    pub fn write_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *writer, self)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orbit_propagration::gravitational_parameter;
+    use crate::planet::{OrbitalElements, DEFAULT_DATA_PATH};
+
+    fn system() -> SolarSystem {
+        SolarSystem::from_path(DEFAULT_DATA_PATH).expect("shipped data should load")
+    }
+
+    /// The shipped system with a comet on a hyperbola given a negative inclination
+    fn with_comet() -> SolarSystem {
+        let mut system = system();
+        let mut comet = system.find("Mercury").expect("Mercury is in the system").clone();
+        comet.orbit_data = OrbitalElements::new([-1E8, 1.5, -20.0, 10.0, 30.0, 0.0, 1E12]);
+        system.bodies.insert("Comet".to_string(), comet);
+        system
+    }
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() <= 1E-9 * expected.abs()
+    }
+
+    #[test]
+    fn earth_orbit_quantities() {
+        let system = system();
+        let earth = system.orbit_summary("Earth").expect("Earth is in the system");
+        // The Earth's own mass counts along with the Sun's
+        let earth_mass = system.find("Earth").expect("Earth is in the system").orbit_data.mass;
+        let mu = gravitational_parameter(system.central_body.mass + earth_mass).in_km3_per_s2();
+        let (a, e) = (earth.semimajor_axis.in_km(), earth.eccentricity);
+        assert_eq!(earth.parent, "Sun");
+        let Some(period) = earth.period else { panic!("the Earth's orbit is closed") };
+        assert!((period.in_days() - 365.25).abs() < 0.1, "{} days", period.in_days());
+        assert!(close(earth.periapsis.in_km(), a * (1.0 - e)));
+        assert!(close(earth.apoapsis.expect("closed orbit").in_km(), a * (1.0 + e)));
+        assert!(close(earth.specific_energy.in_km2_per_s2(), -mu / (2.0 * a)));
+        assert!((earth.periapsis_speed.in_km_per_s() - 30.29).abs() < 0.01, "{}", earth.periapsis_speed);
+        // Angular momentum is the same at both ends of the orbit
+        let h = earth.specific_angular_momentum.in_km2_per_s();
+        assert!(close(earth.periapsis_speed.in_km_per_s() * earth.periapsis.in_km(), h));
+        let (apoapsis, apoapsis_speed) = (earth.apoapsis.expect("closed orbit"), earth.apoapsis_speed.expect("closed orbit"));
+        assert!(close(apoapsis_speed.in_km_per_s() * apoapsis.in_km(), h));
+        assert!(close(earth.semi_latus_rectum.in_km(), h * h / mu));
+        assert!(system.orbit_summary("Vulcan").is_none());
+    }
+
+    #[test]
+    fn hyperbolic_orbits_have_no_far_end() {
+        let system = with_comet();
+        let comet = system.orbit_summary("Comet").expect("the comet is in the system");
+        assert_eq!((comet.period, comet.apoapsis, comet.apoapsis_speed), (None, None, None));
+        assert!(comet.specific_energy.in_km2_per_s2() > 0.0);
+        assert!(close(comet.periapsis.in_km(), 0.5E8));
+        assert!(close(comet.inclination.in_degrees(), 20.0), "{} degrees", comet.inclination.in_degrees());
+
+        let summary = system.summary();
+        let names: Vec<&str> = summary.bodies.iter().map(|orbit| orbit.name.as_str()).collect();
+        assert_eq!(names.len(), 9);
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", names);
+        let mut buffer = Vec::new();
+        summary.write_csv(&mut buffer).expect("writing to memory shouldn't fail");
+        let csv = String::from_utf8(buffer).expect("output should be UTF-8");
+        let row = csv.lines().find(|line| line.starts_with("Comet,")).expect("a row for the comet");
+        assert_eq!(row.split(',').filter(|column| column.is_empty()).count(), 3, "{}", row);
+    }
+}
@@ -0,0 +1,336 @@
+use std::f64::consts::{PI, TAU};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::orbit_propagration::gravitational_parameter;
+use crate::planet::SolarSystem;
+use crate::units::{Angle, AngularRate, GravitationalParameter, Length, Speed, Time};
+use crate::vector::{dot, norm};
+
+/// Golden section steps when splitting a plane change between two burns, narrowing the split to about 1e-10
+const SPLIT_ITERATIONS: usize = 50;
+
+/// A circular orbit to transfer from or to
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct CircularOrbit {
+    pub radius: Length, // from the centre of the body orbited
+    pub inclination: Angle,
+    pub longitude_of_ascending_node: Angle,
+}
+
+impl CircularOrbit {
+    /// Speed on the orbit around a body with gravitational parameter mu
+    pub fn speed(&self, mu: GravitationalParameter) -> Speed {
+        Speed::km_per_s((mu.in_km3_per_s2() / self.radius.in_km()).sqrt())
+    }
+
+    /// Mean motion around a body with gravitational parameter mu
+    pub fn mean_motion(&self, mu: GravitationalParameter) -> AngularRate {
+        AngularRate::radians_per_s((mu.in_km3_per_s2() / self.radius.in_km().powi(3)).sqrt())
+    }
+
+    /// Unit vector along the orbit's angular momentum
+    fn normal(&self) -> [f64; 3] {
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        let (sin_node, cos_node) = self.longitude_of_ascending_node.sin_cos();
+        [sin_i * sin_node, -sin_i * cos_node, cos_i]
+    }
+
+    /// Angle between the planes of the two orbits
+    pub fn relative_inclination(&self, other: &CircularOrbit) -> Angle {
+        Angle::radians((dot(self.normal(), other.normal()) / (norm(self.normal()) * norm(other.normal()))).clamp(-1.0, 1.0).acos())
+    }
+}
+
+impl SolarSystem {
+    /// A planet's orbit around the central body at the epoch, taken as circular at its semimajor axis.
+    /// None if it isn't a planet of the system
+    pub fn planet_orbit(&self, name: &str) -> Option<CircularOrbit> {
+        let elements = &self.bodies.get(name)?.orbit_data;
+        Some(CircularOrbit {
+            radius: elements.semimajor_axis,
+            inclination: elements.inclination,
+            longitude_of_ascending_node: elements.longitude_of_ascending_node,
+        })
+    }
+
+    /// A parking orbit `altitude` above a body's mean radius, None if the body isn't in the system
+    pub fn parking_orbit(&self, name: &str, altitude: Length, inclination: Angle, longitude_of_ascending_node: Angle) -> Option<CircularOrbit> {
+        let body = self.find(name)?;
+        Some(CircularOrbit { radius: body.radius + altitude, inclination, longitude_of_ascending_node })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferKind {
+    Hohmann,
+    BiElliptic,
+    CombinedPlaneChange,
+}
+
+/// Impulsive transfer between two circular orbits around the same body
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub kind: TransferKind,
+    pub burns: Vec<Speed>, // each burn's delta v in order
+    pub plane_changes: Vec<Angle>, // how far each burn turns the orbit's plane
+    pub total_delta_v: Speed,
+    pub transfer_time: Time,
+    pub phase_angle: Angle, // 0 to 2 pi, how far ahead of the craft a target on the final orbit must be at the first burn
+    pub synodic_period: Time, // between repeats of the phase angle, infinite for orbits of the same radius
+}
+
+/// Delta v to turn a velocity of `speed` through `angle` without changing its size
+pub fn plane_change(speed: Speed, angle: Angle) -> Speed {
+    2.0 * speed * (angle / 2.0).sin()
+}
+
+/// Delta v between two velocities of these sizes with `angle` between them
+fn burn(from_speed: Speed, to_speed: Speed, angle: Angle) -> Speed {
+    let (from_speed, to_speed) = (from_speed.in_km_per_s(), to_speed.in_km_per_s());
+    Speed::km_per_s((from_speed * from_speed + to_speed * to_speed - 2.0 * from_speed * to_speed * angle.cos()).max(0.0).sqrt())
+}
+
+/// Speed at `radius` on an orbit with semimajor axis `a`, from the vis-viva equation
+fn vis_viva(mu: GravitationalParameter, radius: Length, a: Length) -> Speed {
+    Speed::km_per_s((mu.in_km3_per_s2() * (2.0 / radius.in_km() - 1.0 / a.in_km())).sqrt())
+}
+
+/// Time for half an orbit with semimajor axis `a`
+fn half_period(mu: GravitationalParameter, a: Length) -> Time {
+    Time::seconds(PI * (a.in_km().powi(3) / mu.in_km3_per_s2()).sqrt())
+}
+
+fn synodic_period(mu: GravitationalParameter, from: &CircularOrbit, to: &CircularOrbit) -> Time {
+    Time::seconds(TAU / (from.mean_motion(mu) - to.mean_motion(mu)).abs().in_radians_per_s())
+}
+
+/// How far ahead of the craft a target on `to` must be at the first burn to meet it after `transfer_time`,
+/// the craft having gone `travelled` round the central body
+fn phase_angle(mu: GravitationalParameter, to: &CircularOrbit, transfer_time: Time, travelled: f64) -> Angle {
+    (Angle::radians(travelled) - to.mean_motion(mu) * transfer_time).wrapped()
+}
+
+/// Two burn transfer along half an ellipse touching both orbits, the cheapest for radius ratios below about 11.94.
+/// The orbits are treated as coplanar, see `combined_plane_change` for inclined ones.
+/// mu is the gravitational parameter of the body both orbits go around
+pub fn hohmann(mu: GravitationalParameter, from: &CircularOrbit, to: &CircularOrbit) -> Transfer {
+    let a = (from.radius + to.radius) / 2.0;
+    let burns = vec![
+        (vis_viva(mu, from.radius, a) - from.speed(mu)).abs(),
+        (to.speed(mu) - vis_viva(mu, to.radius, a)).abs(),
+    ];
+    let transfer_time = half_period(mu, a);
+    Transfer {
+        kind: TransferKind::Hohmann,
+        total_delta_v: burns.iter().copied().sum(),
+        burns,
+        plane_changes: vec![Angle::default(); 2],
+        transfer_time,
+        phase_angle: phase_angle(mu, to, transfer_time, PI),
+        synodic_period: synodic_period(mu, from, to),
+    }
+}
+
+/// Three burn transfer out along half an ellipse to `intermediate_radius`, beyond both orbits, and back down along
+/// half of another. It beats `hohmann` for large radius ratios at the cost of a much longer flight.
+/// The orbits are treated as coplanar, mu is the gravitational parameter of the body both orbits go around.
+/// None if the intermediate radius isn't beyond both orbits
+pub fn bi_elliptic(mu: GravitationalParameter, from: &CircularOrbit, to: &CircularOrbit, intermediate_radius: Length) -> Option<Transfer> {
+    if intermediate_radius.in_km().is_nan() || intermediate_radius < from.radius.max(to.radius) {
+        return None;
+    }
+    let (a_out, a_in) = ((from.radius + intermediate_radius) / 2.0, (to.radius + intermediate_radius) / 2.0);
+    let burns = vec![
+        (vis_viva(mu, from.radius, a_out) - from.speed(mu)).abs(),
+        (vis_viva(mu, intermediate_radius, a_in) - vis_viva(mu, intermediate_radius, a_out)).abs(),
+        (vis_viva(mu, to.radius, a_in) - to.speed(mu)).abs(),
+    ];
+    let transfer_time = half_period(mu, a_out) + half_period(mu, a_in);
+    Some(Transfer {
+        kind: TransferKind::BiElliptic,
+        total_delta_v: burns.iter().copied().sum(),
+        burns,
+        plane_changes: vec![Angle::default(); 3],
+        transfer_time,
+        // The craft arrives a whole turn round from where it left
+        phase_angle: phase_angle(mu, to, transfer_time, TAU),
+        synodic_period: synodic_period(mu, from, to),
+    })
+}
+
+/// `hohmann` between inclined orbits, starting on the line where the two planes cross and turning the plane partly
+/// at each burn, split to use the least delta v. mu is the gravitational parameter of the body both orbits go around
+pub fn combined_plane_change(mu: GravitationalParameter, from: &CircularOrbit, to: &CircularOrbit) -> Transfer {
+    let a = (from.radius + to.radius) / 2.0;
+    // Speeds on the starting orbit, the transfer ellipse at each end and the final orbit
+    let (initial, leaving) = (from.speed(mu), vis_viva(mu, from.radius, a));
+    let (arriving, last) = (vis_viva(mu, to.radius, a), to.speed(mu));
+    let inclination = from.relative_inclination(to);
+    let cost = |split: f64| {
+        [burn(initial, leaving, split * inclination), burn(arriving, last, (1.0 - split) * inclination)]
+    };
+    // Golden section search for the fraction of the plane change made at the first burn
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..SPLIT_ITERATIONS {
+        let (left, right) = (high - ratio * (high - low), low + ratio * (high - low));
+        if cost(left).into_iter().sum::<Speed>() < cost(right).into_iter().sum::<Speed>() {
+            high = right;
+        }
+        else {
+            low = left;
+        }
+    }
+    let split = (low + high) / 2.0;
+    let burns = cost(split).to_vec();
+    let transfer_time = half_period(mu, a);
+    Transfer {
+        kind: TransferKind::CombinedPlaneChange,
+        total_delta_v: burns.iter().copied().sum(),
+        burns,
+        plane_changes: vec![split * inclination, (1.0 - split) * inclination],
+        transfer_time,
+        phase_angle: phase_angle(mu, to, transfer_time, PI),
+        synodic_period: synodic_period(mu, from, to),
+    }
+}
+
+/// Every kind of transfer from one planet to another
+#[derive(Serialize, Debug, Clone)]
+pub struct TransferRow {
+    pub from: String,
+    pub to: String,
+    pub transfers: Vec<Transfer>,
+}
+
+/// Transfers between every ordered pair of planets in a system around its central body
+#[derive(Serialize, Debug, Clone)]
+pub struct TransferTable {
+    pub center: String,
+    pub bi_elliptic_ratio: f64, // intermediate radius over the larger of the two orbit radii
+    pub rows: Vec<TransferRow>,
+}
+
+impl TransferTable {
+    /// Hohmann, bi-elliptic and combined plane change transfers between the planets' orbits at the epoch (see
+    /// `SolarSystem::planet_orbit`), in order of distance from the central body. Bi-elliptic transfers climb to
+    /// `bi_elliptic_ratio` times the larger radius of each pair. None unless the ratio is at least 1, as the intermediate
+    /// orbit has to lie beyond both
+    pub fn new(system: &SolarSystem, bi_elliptic_ratio: f64) -> Option<Self> {
+        if bi_elliptic_ratio.is_nan() || bi_elliptic_ratio < 1.0 {
+            return None;
+        }
+        let mu = gravitational_parameter(system.central_body.mass);
+        let mut planets: Vec<(&String, CircularOrbit)> =
+            system.bodies.keys().filter_map(|name| Some((name, system.planet_orbit(name)?))).collect();
+        planets.sort_by(|a, b| a.1.radius.in_km().total_cmp(&b.1.radius.in_km()).then_with(|| a.0.cmp(b.0)));
+        let mut rows = Vec::new();
+        for (from_name, from) in &planets {
+            for (to_name, to) in planets.iter().filter(|(to_name, _)| to_name != from_name) {
+                let intermediate_radius = bi_elliptic_ratio * from.radius.max(to.radius);
+                rows.push(TransferRow {
+                    from: from_name.to_string(),
+                    to: to_name.to_string(),
+                    transfers: vec![
+                        hohmann(mu, from, to),
+                        bi_elliptic(mu, from, to, intermediate_radius)?,
+                        combined_plane_change(mu, from, to),
+                    ],
+                });
+            }
+        }
+        Some(Self { center: system.central_body.name.clone(), bi_elliptic_ratio, rows })
+    }
+
+    /// Comma separated values, one row per pair and kind of transfer with the burns separated by semicolons.
+    /// Lines starting with # before the column names give the central body and units
+    pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "# transfers around {}, bi-elliptic ratio {}", self.center, self.bi_elliptic_ratio)?;
+        writeln!(writer, "# units: speeds km/s, times days, angles degrees")?;
+        writeln!(
+            writer,
+            "from,to,kind,total_delta_v_km_s,burns_km_s,plane_changes_degrees,transfer_time_days,phase_angle_degrees,synodic_period_days"
+        )?;
+        let join = |values: &mut dyn Iterator<Item = f64>| values.map(|value| value.to_string()).collect::<Vec<_>>().join(";");
+        for row in &self.rows {
+            for transfer in &row.transfers {
+                writeln!(
+                    writer,
+                    "{},{},{:?},{},{},{},{},{},{}",
+                    row.from,
+                    row.to,
+                    transfer.kind,
+                    transfer.total_delta_v.in_km_per_s(),
+                    join(&mut transfer.burns.iter().map(|burn| burn.in_km_per_s())),
+                    join(&mut transfer.plane_changes.iter().map(|angle| angle.in_degrees())),
+                    transfer.transfer_time.in_days(),
+                    transfer.phase_angle.in_degrees(),
+                    transfer.synodic_period.in_days()
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pretty printed JSON of the whole table, in the units the structs hold (km/s, s, radians)
+    pub fn write_json(&self, writer: &mut impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *writer, self)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUN: GravitationalParameter = GravitationalParameter::km3_per_s2(1.32712440018e11);
+
+    fn circular(radius: Length) -> CircularOrbit {
+        CircularOrbit { radius, inclination: Angle::default(), longitude_of_ascending_node: Angle::default() }
+    }
+
+    fn close(actual: f64, expected: f64, tolerance: f64) -> bool {
+        (actual - expected).abs() <= tolerance
+    }
+
+    #[test]
+    fn hohmann_from_earth_to_mars() {
+        let transfer = hohmann(SUN, &circular(Length::au(1.0)), &circular(Length::au(1.524)));
+        let burns: Vec<f64> = transfer.burns.iter().map(|burn| burn.in_km_per_s()).collect();
+        assert!(close(burns[0], 2.94, 0.01) && close(burns[1], 2.65, 0.01), "{:?} km/s", burns);
+        assert!(close(transfer.total_delta_v.in_km_per_s(), burns[0] + burns[1], 1e-12));
+        assert!(close(transfer.transfer_time.in_days(), 259.0, 1.0), "{} days", transfer.transfer_time.in_days());
+        assert!(close(transfer.phase_angle.in_degrees(), 44.3, 0.2), "{} degrees", transfer.phase_angle.in_degrees());
+        assert!(close(transfer.synodic_period.in_days(), 780.0, 2.0), "{} days", transfer.synodic_period.in_days());
+    }
+
+    #[test]
+    fn bi_elliptic_beats_hohmann_for_a_large_radius_ratio() {
+        let (from, to) = (circular(Length::au(1.0)), circular(Length::au(20.0)));
+        let direct = hohmann(SUN, &from, &to);
+        let Some(climb) = bi_elliptic(SUN, &from, &to, Length::au(200.0)) else {
+            panic!("200 AU is beyond both orbits");
+        };
+        assert_eq!(climb.burns.len(), 3);
+        assert!(climb.total_delta_v < direct.total_delta_v, "{} against {}", climb.total_delta_v, direct.total_delta_v);
+        assert!(climb.transfer_time > direct.transfer_time);
+        assert!(bi_elliptic(SUN, &from, &to, Length::au(10.0)).is_none());
+        assert!(bi_elliptic(SUN, &from, &to, Length::km(f64::NAN)).is_none());
+    }
+
+    #[test]
+    fn combined_plane_change_beats_separate_burns() {
+        let from = circular(Length::au(1.0));
+        let to = CircularOrbit { inclination: Angle::degrees(10.0), ..circular(Length::au(1.524)) };
+        let coplanar = hohmann(SUN, &from, &to);
+        let combined = combined_plane_change(SUN, &from, &to);
+        let separate = coplanar.total_delta_v + plane_change(to.speed(SUN), Angle::degrees(10.0));
+        assert!(combined.total_delta_v > coplanar.total_delta_v && combined.total_delta_v < separate);
+        let turned = combined.plane_changes.iter().map(|angle| angle.in_degrees()).sum::<f64>();
+        assert!(close(turned, 10.0, 1e-9), "{} degrees", turned);
+        let flat = combined_plane_change(SUN, &from, &circular(Length::au(1.524)));
+        assert!(close(flat.total_delta_v.in_km_per_s(), coplanar.total_delta_v.in_km_per_s(), 1e-9));
+    }
+}
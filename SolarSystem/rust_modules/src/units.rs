@@ -0,0 +1,641 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use serde::Serialize;
+
+use crate::epoch::{DAYS_PER_JULIAN_CENTURY, SECONDS_PER_DAY};
+use crate::kepler::wrap_angle;
+use crate::vector::norm;
+
+/// Gravitational constant in SI units, m^3/(kg s^2)
+pub const GRAVITATIONAL_CONSTANT: f64 = 6.6743E-11;
+/// Metres in a kilometre, for the parts of the crate that work in SI
+pub(crate) const M_PER_KM: f64 = 1E3;
+/// Kilometres in an astronomical unit
+pub const KM_PER_AU: f64 = 149597870.7;
+const DAYS_PER_JULIAN_YEAR: f64 = 365.25;
+const ARCSECONDS_PER_RADIAN: f64 = 648000.0 / std::f64::consts::PI;
+
+/// Arithmetic shared by every quantity: sums and differences of the same kind, scaling by plain numbers
+/// and ratios of two of the same kind, which come out as plain numbers
+macro_rules! quantity {
+    ($name:ident, $unit:literal) => {
+        impl Add for $name {
+            type Output = Self;
+            fn add(self, other: Self) -> Self {
+                Self(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 - other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Self(-self.0)
+            }
+        }
+
+        impl Mul<f64> for $name {
+            type Output = Self;
+            fn mul(self, factor: f64) -> Self {
+                Self(self.0 * factor)
+            }
+        }
+
+        impl Mul<$name> for f64 {
+            type Output = $name;
+            fn mul(self, quantity: $name) -> $name {
+                $name(self * quantity.0)
+            }
+        }
+
+        impl Div<f64> for $name {
+            type Output = Self;
+            fn div(self, divisor: f64) -> Self {
+                Self(self.0 / divisor)
+            }
+        }
+
+        impl Div for $name {
+            type Output = f64;
+            fn div(self, other: Self) -> f64 {
+                self.0 / other.0
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                Self(iter.map(|quantity| quantity.0).sum())
+            }
+        }
+
+        impl $name {
+            /// The smaller of two quantities
+            pub fn min(self, other: Self) -> Self {
+                Self(self.0.min(other.0))
+            }
+
+            /// The larger of two quantities
+            pub fn max(self, other: Self) -> Self {
+                Self(self.0.max(other.0))
+            }
+
+            /// Size of the quantity, dropping its sign
+            pub fn abs(self) -> Self {
+                Self(self.0.abs())
+            }
+
+            /// Total order for sorting, as `f64::total_cmp`
+            pub fn total_cmp(&self, other: &Self) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{} {}", self.0, $unit)
+            }
+        }
+    };
+}
+
+/// A distance, kept in km. Serialised as km
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Length(f64);
+
+impl Length {
+    pub const fn km(km: f64) -> Self {
+        Self(km)
+    }
+
+    pub fn m(m: f64) -> Self {
+        Self(m / M_PER_KM)
+    }
+
+    pub fn au(au: f64) -> Self {
+        Self(au * KM_PER_AU)
+    }
+
+    pub fn in_km(self) -> f64 {
+        self.0
+    }
+
+    pub fn in_m(self) -> f64 {
+        self.0 * M_PER_KM
+    }
+
+    pub fn in_au(self) -> f64 {
+        self.0 / KM_PER_AU
+    }
+}
+
+/// A mass, kept in kg. Serialised as kg
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Mass(f64);
+
+impl Mass {
+    pub const fn kg(kg: f64) -> Self {
+        Self(kg)
+    }
+
+    pub fn in_kg(self) -> f64 {
+        self.0
+    }
+}
+
+/// A span of time, or a time measured from an epoch, kept in seconds. Serialised as seconds
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Time(f64);
+
+impl Time {
+    pub const fn seconds(seconds: f64) -> Self {
+        Self(seconds)
+    }
+
+    pub fn days(days: f64) -> Self {
+        Self(days * SECONDS_PER_DAY)
+    }
+
+    /// Julian years of 365.25 days
+    pub fn years(years: f64) -> Self {
+        Self::days(years * DAYS_PER_JULIAN_YEAR)
+    }
+
+    pub fn in_seconds(self) -> f64 {
+        self.0
+    }
+
+    pub fn in_days(self) -> f64 {
+        self.0 / SECONDS_PER_DAY
+    }
+
+    pub fn in_years(self) -> f64 {
+        self.in_days() / DAYS_PER_JULIAN_YEAR
+    }
+}
+
+/// An angle, kept in radians. Serialised as radians
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Angle(f64);
+
+impl Angle {
+    pub const fn radians(radians: f64) -> Self {
+        Self(radians)
+    }
+
+    pub fn degrees(degrees: f64) -> Self {
+        Self(degrees.to_radians())
+    }
+
+    pub fn in_radians(self) -> f64 {
+        self.0
+    }
+
+    pub fn in_degrees(self) -> f64 {
+        self.0.to_degrees()
+    }
+
+    /// The same direction between 0 and 2 pi
+    pub fn wrapped(self) -> Self {
+        Self(wrap_angle(self.0))
+    }
+
+    pub fn sin(self) -> f64 {
+        self.0.sin()
+    }
+
+    pub fn cos(self) -> f64 {
+        self.0.cos()
+    }
+
+    pub fn sin_cos(self) -> (f64, f64) {
+        self.0.sin_cos()
+    }
+}
+
+/// A gravitational parameter G M, kept in km^3/s^2. Serialised as km^3/s^2.
+/// Converting from a mass applies the gravitational constant, taking care of the SI metres it comes in
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct GravitationalParameter(f64);
+
+impl GravitationalParameter {
+    pub const fn km3_per_s2(km3_per_s2: f64) -> Self {
+        Self(km3_per_s2)
+    }
+
+    pub fn m3_per_s2(m3_per_s2: f64) -> Self {
+        Self(m3_per_s2 / M_PER_KM.powi(3))
+    }
+
+    pub fn in_km3_per_s2(self) -> f64 {
+        self.0
+    }
+
+    pub fn in_m3_per_s2(self) -> f64 {
+        self.0 * M_PER_KM.powi(3)
+    }
+}
+
+/// A speed, kept in km/s. Serialised as km/s
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Speed(f64);
+
+impl Speed {
+    pub const fn km_per_s(km_per_s: f64) -> Self {
+        Self(km_per_s)
+    }
+
+    pub fn m_per_s(m_per_s: f64) -> Self {
+        Self(m_per_s / M_PER_KM)
+    }
+
+    pub fn in_km_per_s(self) -> f64 {
+        self.0
+    }
+
+    pub fn in_m_per_s(self) -> f64 {
+        self.0 * M_PER_KM
+    }
+}
+
+/// How fast an angle turns, kept in radians/s. Serialised as radians/s
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct AngularRate(f64);
+
+impl AngularRate {
+    pub const fn radians_per_s(radians_per_s: f64) -> Self {
+        Self(radians_per_s)
+    }
+
+    pub fn in_radians_per_s(self) -> f64 {
+        self.0
+    }
+
+    pub fn arcseconds_per_century(arcseconds_per_century: f64) -> Self {
+        Self(arcseconds_per_century / ARCSECONDS_PER_RADIAN / (DAYS_PER_JULIAN_CENTURY * SECONDS_PER_DAY))
+    }
+
+    pub fn in_degrees_per_day(self) -> f64 {
+        self.0.to_degrees() * SECONDS_PER_DAY
+    }
+
+    /// Per Julian century, the usual unit for apsidal precession
+    pub fn in_arcseconds_per_century(self) -> f64 {
+        self.0 * ARCSECONDS_PER_RADIAN * DAYS_PER_JULIAN_CENTURY * SECONDS_PER_DAY
+    }
+}
+
+/// The angle turned through at this rate over a span of time
+impl Mul<Time> for AngularRate {
+    type Output = Angle;
+    fn mul(self, time: Time) -> Angle {
+        Angle(self.0 * time.0)
+    }
+}
+
+/// Angular momentum per unit mass of an orbit, |r x v|, kept in km^2/s. Serialised as km^2/s
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct SpecificAngularMomentum(f64);
+
+impl SpecificAngularMomentum {
+    pub const fn km2_per_s(km2_per_s: f64) -> Self {
+        Self(km2_per_s)
+    }
+
+    pub fn in_km2_per_s(self) -> f64 {
+        self.0
+    }
+}
+
+/// Orbital energy per unit mass, or the square of a speed such as C3, kept in km^2/s^2. Serialised as km^2/s^2
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct SpecificEnergy(f64);
+
+impl SpecificEnergy {
+    pub const fn km2_per_s2(km2_per_s2: f64) -> Self {
+        Self(km2_per_s2)
+    }
+
+    pub fn in_km2_per_s2(self) -> f64 {
+        self.0
+    }
+}
+
+/// The square of a speed, e.g. C3 from the hyperbolic excess speed
+impl Mul for Speed {
+    type Output = SpecificEnergy;
+    fn mul(self, other: Self) -> SpecificEnergy {
+        SpecificEnergy(self.0 * other.0)
+    }
+}
+
+/// An energy, kept in kg km^2/s^2 (MJ) to go with the crate's km and km/s. Serialised as kg km^2/s^2
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Energy(f64);
+
+impl Energy {
+    pub const fn kg_km2_per_s2(kg_km2_per_s2: f64) -> Self {
+        Self(kg_km2_per_s2)
+    }
+
+    pub fn joules(joules: f64) -> Self {
+        Self(joules / M_PER_KM.powi(2))
+    }
+
+    pub fn in_kg_km2_per_s2(self) -> f64 {
+        self.0
+    }
+
+    pub fn in_joules(self) -> f64 {
+        self.0 * M_PER_KM.powi(2)
+    }
+}
+
+/// An area, kept in km^2. Serialised as km^2
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Area(f64);
+
+impl Area {
+    pub const fn km2(km2: f64) -> Self {
+        Self(km2)
+    }
+
+    pub fn m2(m2: f64) -> Self {
+        Self(m2 / M_PER_KM.powi(2))
+    }
+
+    pub fn in_km2(self) -> f64 {
+        self.0
+    }
+
+    pub fn in_m2(self) -> f64 {
+        self.0 * M_PER_KM.powi(2)
+    }
+}
+
+/// A mass density, kept in kg/m^3 as atmosphere models give it. Serialised as kg/m^3
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Density(f64);
+
+impl Density {
+    pub const fn kg_per_m3(kg_per_m3: f64) -> Self {
+        Self(kg_per_m3)
+    }
+
+    pub fn in_kg_per_m3(self) -> f64 {
+        self.0
+    }
+}
+
+/// A moment given as a Julian date in TDB, kept in days. Serialised as days.
+/// Dates differ by a `Time` and move by one, but can't be added together
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct JulianDate(f64);
+
+impl JulianDate {
+    pub const fn days(days: f64) -> Self {
+        Self(days)
+    }
+
+    pub fn in_days(self) -> f64 {
+        self.0
+    }
+
+    /// Total order for sorting by date, as `f64::total_cmp`
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Add<Time> for JulianDate {
+    type Output = Self;
+    fn add(self, time: Time) -> Self {
+        Self(self.0 + time.in_days())
+    }
+}
+
+impl Sub<Time> for JulianDate {
+    type Output = Self;
+    fn sub(self, time: Time) -> Self {
+        Self(self.0 - time.in_days())
+    }
+}
+
+/// Time from one date to another
+impl Sub for JulianDate {
+    type Output = Time;
+    fn sub(self, other: Self) -> Time {
+        Time::days(self.0 - other.0)
+    }
+}
+
+impl fmt::Display for JulianDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JD {}", self.0)
+    }
+}
+
+/// A position vector, kept in km. Serialised as an array of km
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Position([f64; 3]);
+
+impl Position {
+    pub const fn km(km: [f64; 3]) -> Self {
+        Self(km)
+    }
+
+    pub fn in_km(self) -> [f64; 3] {
+        self.0
+    }
+
+    /// Distance from the origin of the frame
+    pub fn magnitude(self) -> Length {
+        Length(norm(self.0))
+    }
+}
+
+/// A velocity vector, kept in km/s. Serialised as an array of km/s
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Velocity([f64; 3]);
+
+impl Velocity {
+    pub const fn km_per_s(km_per_s: [f64; 3]) -> Self {
+        Self(km_per_s)
+    }
+
+    pub fn in_km_per_s(self) -> [f64; 3] {
+        self.0
+    }
+
+    pub fn magnitude(self) -> Speed {
+        Speed(norm(self.0))
+    }
+}
+
+/// A linear momentum vector, kept in kg km/s. Serialised as an array of kg km/s
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(transparent)]
+pub struct LinearMomentum([f64; 3]);
+
+impl LinearMomentum {
+    pub const fn kg_km_per_s(kg_km_per_s: [f64; 3]) -> Self {
+        Self(kg_km_per_s)
+    }
+
+    pub fn in_kg_km_per_s(self) -> [f64; 3] {
+        self.0
+    }
+}
+
+/// An angular momentum vector, kept in kg km^2/s. Serialised as an array of kg km^2/s
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(transparent)]
+pub struct AngularMomentum([f64; 3]);
+
+impl AngularMomentum {
+    pub const fn kg_km2_per_s(kg_km2_per_s: [f64; 3]) -> Self {
+        Self(kg_km2_per_s)
+    }
+
+    pub fn in_kg_km2_per_s(self) -> [f64; 3] {
+        self.0
+    }
+}
+
+/// An acceleration vector, kept in km/s^2. Serialised as an array of km/s^2
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Acceleration([f64; 3]);
+
+impl Acceleration {
+    pub const fn km_per_s2(km_per_s2: [f64; 3]) -> Self {
+        Self(km_per_s2)
+    }
+
+    pub fn in_km_per_s2(self) -> [f64; 3] {
+        self.0
+    }
+
+    pub fn in_m_per_s2(self) -> [f64; 3] {
+        self.0.map(|component| component * M_PER_KM)
+    }
+}
+
+impl From<Mass> for GravitationalParameter {
+    fn from(mass: Mass) -> Self {
+        Self::m3_per_s2(GRAVITATIONAL_CONSTANT * mass.in_kg())
+    }
+}
+
+quantity!(Length, "km");
+quantity!(Mass, "kg");
+quantity!(Time, "s");
+quantity!(Angle, "rad");
+quantity!(GravitationalParameter, "km^3/s^2");
+quantity!(Speed, "km/s");
+quantity!(AngularRate, "rad/s");
+quantity!(SpecificAngularMomentum, "km^2/s");
+quantity!(SpecificEnergy, "km^2/s^2");
+quantity!(Energy, "kg km^2/s^2");
+quantity!(Area, "km^2");
+quantity!(Density, "kg/m^3");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch::J2000;
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() <= 1E-12 * expected.abs().max(1.0)
+    }
+
+    #[test]
+    fn lengths_times_and_angles_convert() {
+        assert!(close(Length::au(1.0).in_km(), KM_PER_AU));
+        assert!(close(Length::m(1500.0).in_km(), 1.5));
+        assert!(close(Length::km(KM_PER_AU).in_au(), 1.0) && close(Length::km(2.0).in_m(), 2000.0));
+        assert!(close(Time::days(1.0).in_seconds(), 86400.0));
+        assert!(close(Time::years(1.0).in_days(), 365.25));
+        assert!(close(Angle::degrees(180.0).in_radians(), std::f64::consts::PI));
+        assert!(close(Angle::degrees(-90.0).wrapped().in_degrees(), 270.0));
+        assert!(close(Speed::m_per_s(7800.0).in_km_per_s(), 7.8));
+        assert!(close(Area::m2(4E6).in_km2(), 4.0));
+    }
+
+    #[test]
+    fn derived_quantities_convert() {
+        // The Earth's mass gives its GM to the precision of G, a few parts in 1e5
+        let earth = GravitationalParameter::from(Mass::kg(5.9722E24));
+        assert!(close(earth.in_km3_per_s2(), GRAVITATIONAL_CONSTANT * 5.9722E24 / 1E9));
+        assert!((earth.in_km3_per_s2() - 398600.4).abs() < 5.0, "{}", earth);
+        assert!(close(GravitationalParameter::m3_per_s2(1E9).in_km3_per_s2(), 1.0));
+        assert!(close((Speed::km_per_s(3.0) * Speed::km_per_s(3.0)).in_km2_per_s2(), 9.0));
+        assert!(close(Energy::joules(1E6).in_kg_km2_per_s2(), 1.0));
+        assert!(close(Energy::kg_km2_per_s2(2.0).in_joules(), 2E6));
+        assert!(close(Acceleration::km_per_s2([1E-3, 0.0, 0.0]).in_m_per_s2()[0], 1.0));
+        // Mercury's relativistic perihelion advance, 43"/century, is about 2.3e-6 degrees a day
+        let advance = AngularRate::arcseconds_per_century(43.0);
+        assert!(close(advance.in_arcseconds_per_century(), 43.0));
+        assert!((advance.in_degrees_per_day() - 43.0 / 3600.0 / 36525.0).abs() < 1E-18);
+        assert!(close((AngularRate::radians_per_s(2.0) * Time::seconds(3.0)).in_radians(), 6.0));
+    }
+
+    #[test]
+    fn julian_dates_step_by_times() {
+        let later = J2000 + Time::days(1.5);
+        assert!(close(later.in_days(), 2451546.5));
+        assert!(close((later - J2000).in_days(), 1.5));
+        assert!(close((later - Time::seconds(43200.0)).in_days(), 2451546.0));
+        assert_eq!(J2000.total_cmp(&later), Ordering::Less);
+        assert_eq!(J2000.to_string(), "JD 2451545");
+    }
+
+    #[test]
+    fn quantities_add_scale_and_compare() {
+        let (short, long) = (Length::km(1.0), Length::km(3.0));
+        assert_eq!(short + long, Length::km(4.0));
+        assert_eq!(long - short, Length::km(2.0));
+        assert_eq!(2.0 * short, short * 2.0);
+        assert_eq!(long / short, 3.0);
+        assert_eq!((-long).abs(), long);
+        assert_eq!((short.min(long), short.max(long)), (short, long));
+        assert_eq!([short, long].into_iter().sum::<Length>(), Length::km(4.0));
+        assert_eq!(Position::km([3.0, 4.0, 0.0]).magnitude(), Length::km(5.0));
+        assert_eq!(short.to_string(), "1 km");
+    }
+}